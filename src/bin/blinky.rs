@@ -1,46 +1,532 @@
 #![no_std]
 #![no_main]
 
+use cortex_m_rt::entry;
 use defmt::*;
-use embassy_executor::Spawner;
-use embassy_stm32::gpio::{Level, Output, Speed, Pull};
+use embassy_executor::raw::TaskStorage;
+use embassy_executor::{Executor, InterruptExecutor, SendSpawner, SpawnToken, Spawner};
+use embassy_stm32::gpio::{Input, Level, Output, OutputType, Speed, Pull};
 use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::flash::{Blocking, Flash};
 use embassy_stm32::usart::{Config, Uart};
-use embassy_stm32::adc::{Adc, Resolution, SampleTime};
+use embassy_stm32::adc::{Adc, RingBufferedAdc, Resolution, SampleTime, Sequence};
+use embassy_stm32::pac::adc::vals::Exten;
 use embassy_stm32::peripherals;
-use embassy_time::{Timer, Instant};
+use embassy_stm32::time::hz;
+use embassy_stm32::timer::low_level::CountingMode;
+use embassy_stm32::timer::qei::{Qei, QeiPin};
+use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm, SimplePwmChannel};
+use cortex_m::peripheral::DWT;
+use cortex_m::singleton;
+use embassy_time::{Duration, Ticker, Timer, Instant};
 use embassy_sync::signal::Signal;
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use heapless::String;
+use embassy_sync::channel::Channel;
+use embassy_sync::pubsub::PubSubChannel;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_futures::select::{select, select3, Either, Either3};
+use core::cell::{Cell, RefCell};
+use core::future::{pending, Future};
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use embassy_stm32::bind_interrupts;
+use embassy_stm32::interrupt;
+use embassy_stm32::interrupt::{InterruptExt, Priority};
+use static_cell::StaticCell;
+use seguidor::registro_comandos;
+use seguidor::botao::{Botao, EventoBotao};
+use seguidor::compartilhado::Compartilhado;
+use seguidor::tarefas::{RegistroTarefas, MAX_TAREFAS};
+use seguidor::tempo_real::{Faixa, Histograma, MonitorRt, MAX_TAREFAS_RT, NUM_FAIXAS, TAMANHO_RASTRO};
+use seguidor::console::{candidatos, divide_comando, escreve, prefixo_comum, Command, ErroComando, Write};
+use seguidor::editor::{EditorLinha, EventoLinha};
+#[cfg(feature = "heap")]
+use seguidor::heap::Alocador;
+use seguidor::encoder::{calcula_velocidade, ContadorEstendido, GeometriaRoda, VelocidadeRoda};
+use seguidor::leds::{ciclo_ativo, Padrao, Tocador, BRILHO_MAX};
+use seguidor::memoria::{self, UsoPilha};
+use seguidor::modo::{CodigoFalha, MaquinaModo, Modo, TransicaoInvalida};
+use seguidor::mapa::{Aprendizado, ErroMapa, MapaPista, PerfilVelocidade, TAMANHO_MAPA_SERIALIZADO};
+use seguidor::marcadores::{ContadorVoltas, DetectorMarcadores, EventoVolta, Marcador};
+use seguidor::motores::{ComandoMotores, Lado, Parada, DUTY_MAX, FREQUENCIA_MAX_HZ, FREQUENCIA_MIN_HZ, FREQUENCIA_PADRAO_HZ};
+use seguidor::ponte::{self, Motores};
+use seguidor::odometria::{Odometria, Pose};
+use seguidor::parametros::{mesmo_nome, nomes_parametros, Parametro, TabelaParametros, Valor};
+use seguidor::pid::{erro_posicao, GanhosPid, Pid};
+use seguidor::velocidade::{limita_alvo, ControleVelocidade, ParametrosVelocidade, VELOCIDADE_MAX_MM_S};
+use seguidor::sensores::{escala_bruta, Calibracao, EstimadorPosicao, LeituraLinha, NUM_SENSORES, POSICAO_CENTRO};
 use {defmt_rtt as _, panic_probe as _};
 
-// BUTTON_SIGNAL é um sinal para notificar eventos de botão
-static BUTTON_SIGNAL: Signal<ThreadModeRawMutex, ()> = Signal::new(); 
+// Comandos do console, agrupados pelo subsistema que cada um mexe
+mod controle;
+mod mapa;
+mod motores;
+mod odometria;
+mod parametros;
+mod sensores;
+mod sistema;
 
-// Estrutura para armazenar estatísticas do sistema
+// LEDs de estado: PC13 mostra o modo e os códigos de falha, PA11 a calibração
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum Led {
+    Estado,
+    Calibracao,
+}
+
+// Só o último padrão importa: um novo sobrescreve o que a tarefa ainda não leu
+static PADRAO_ESTADO: Signal<CriticalSectionRawMutex, Padrao> = Signal::new();
+static PADRAO_CALIBRACAO: Signal<CriticalSectionRawMutex, Padrao> = Signal::new();
+
+impl Led {
+    fn sinal(&self) -> &'static Signal<CriticalSectionRawMutex, Padrao> {
+        match self {
+            Led::Estado => &PADRAO_ESTADO,
+            Led::Calibracao => &PADRAO_CALIBRACAO,
+        }
+    }
+
+    // Parâmetro com o ritmo base do padrão do LED
+    fn ritmo(&self) -> Param {
+        match self {
+            Led::Estado => Param::Led1Ms,
+            Led::Calibracao => Param::Led2Ms,
+        }
+    }
+
+    // Padrão que o LED deve mostrar agora, refeito quando o ritmo muda
+    fn padrao_atual(&self) -> Padrao {
+        match self {
+            Led::Estado => padrao_modo(modo_atual()),
+            Led::Calibracao if CALIBRANDO.load(Ordering::Relaxed) => padrao_varredura(),
+            Led::Calibracao => padrao_calibracao(&CALIBRACAO.lock(|c| *c.borrow())),
+        }
+    }
+}
+
+fn mostra_padrao(led: Led, padrao: Padrao) {
+    led.sinal().signal(padrao);
+}
+
+// Tempos proporcionais a led1_ms; com o padrão de 200 ms o Calibrando pisca a 100 ms.
+// O PC13 só acende e apaga, então nada de Nivel ou Respira aqui
+fn padrao_modo(modo: Modo) -> Padrao {
+    let ritmo = parametro(Param::Led1Ms).como_u32();
+    match modo {
+        Modo::Ocioso => Padrao::Batimento(ritmo * 5),
+        Modo::Calibrando => Padrao::Pisca(ritmo / 2),
+        Modo::Armado => Padrao::Pisca(ritmo),
+        Modo::Seguindo => Padrao::Aceso,
+        Modo::Parado => Padrao::Pisca(ritmo * 5 / 2),
+        Modo::Falha => Padrao::Codigo(MODO.com(|m| m.falha()).map_or(1, |f| f.piscadas())),
+        Modo::Bancada => Padrao::Pisca(ritmo * 5),
+    }
+}
+
+// Aceso com calibração válida, fraco com uma varredura que não viu a linha
+// em todos os sensores e apagado sem calibração nenhuma
+fn padrao_calibracao(cal: &Calibracao) -> Padrao {
+    if cal.valida() {
+        Padrao::Aceso
+    } else if cal.amostras > 0 {
+        Padrao::Nivel(BRILHO_MAX / 4)
+    } else {
+        Padrao::Apagado
+    }
+}
+
+// LED de calibração respirando durante a varredura
+fn padrao_varredura() -> Padrao {
+    Padrao::Respira(parametro(Param::Led2Ms).como_u32())
+}
+
+// Limite de brilho em %; seguindo a linha os LEDs ficam mais fracos
+fn limite_brilho() -> u8 {
+    let p = if modo_atual() == Modo::Seguindo { Param::BrilhoCorrida } else { Param::BrilhoLeds };
+    parametro(p).como_u32() as u8
+}
+
+// PA11 é o TIM1_CH4, que sobra no timer do gatilho do ADC. O PC13 não tem canal
+// de timer e só acende ou apaga: brilho, limite e respiração ficam com o PA11
+enum SaidaLed {
+    Pwm(SimplePwmChannel<'static, peripherals::TIM1>),
+    Digital(Output<'static>),
+}
+
+impl SaidaLed {
+    fn pwm(mut canal: SimplePwmChannel<'static, peripherals::TIM1>) -> Self {
+        canal.set_duty_cycle(0);
+        canal.enable();
+        SaidaLed::Pwm(canal)
+    }
+
+    fn digital(pino: Output<'static>) -> Self {
+        SaidaLed::Digital(pino)
+    }
+
+    // Ciclo ativo de 0 a u16::MAX, já com a curva gama; no PC13 qualquer ciclo acende
+    fn define(&mut self, ciclo: u16) {
+        match self {
+            SaidaLed::Pwm(canal) => {
+                let ativo = ciclo as u32 * canal.max_duty_cycle() as u32 / u16::MAX as u32;
+                canal.set_duty_cycle(ativo as u16);
+            }
+            SaidaLed::Digital(pino) => {
+                if ciclo > 0 {
+                    pino.set_high();
+                } else {
+                    pino.set_low();
+                }
+            }
+        }
+    }
+}
+
+// Gestos do botão da placa, para quem precisar deles (modo de operação)
+static BOTAO: PubSubChannel<CriticalSectionRawMutex, EventoBotao, 4, 4, 0> = PubSubChannel::new();
+
+// Amostragem do botão enquanto ele não está ocioso (debounce e tempos dos gestos)
+const BOTAO_PERIODO_MS: u64 = 5;
+
+// CALIBRA_SIGNAL inicia a calibração dos sensores ao entrar no modo Calibrando
+static CALIBRA_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+// Sair de Calibrando antes do fim interrompe a varredura
+static CANCELA_CALIBRACAO: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static CALIBRACAO: Mutex<CriticalSectionRawMutex, RefCell<Calibracao>> = Mutex::new(RefCell::new(Calibracao::new()));
+static CALIBRANDO: AtomicBool = AtomicBool::new(false);
+
+// Período da malha de controle (tarefa de tempo real hard)
+const CONTROLE_PERIODO_US: u64 = 2000;
+
+// Período de amostragem dos encoders para o cálculo de velocidade
+const ENCODER_PERIODO_MS: u64 = 10;
+
+// A malha de controle só comanda as rodas depois do comando 'seguir'
+static SEGUINDO: AtomicBool = AtomicBool::new(false);
+
+// Com a malha de velocidade ativa o encoder_task aciona os motores a partir de ALVO_VELOCIDADE
+static MALHA_VELOCIDADE: AtomicBool = AtomicBool::new(false);
+
+// Pedido de zerar a pose, atendido pelo encoder_task que é dono da odometria
+static ZERA_POSE: AtomicBool = AtomicBool::new(false);
+
+// Velocidade alvo (esquerda, direita) em mm/s
+static ALVO_VELOCIDADE: Mutex<CriticalSectionRawMutex, Cell<(f32, f32)>> = Mutex::new(Cell::new((0.0, 0.0)));
+
+// Volta de aprendizado e voltas seguintes usando o mapa da pista
+const PISTA_LIVRE: u8 = 0;
+const PISTA_APRENDENDO: u8 = 1;
+const PISTA_MAPA: u8 = 2;
+static MODO_PISTA: AtomicU8 = AtomicU8::new(PISTA_LIVRE);
+
+const PERFIL_PISTA: PerfilVelocidade = PerfilVelocidade::padrao();
+
+static MAPA: Mutex<CriticalSectionRawMutex, RefCell<MapaPista>> = Mutex::new(RefCell::new(MapaPista::new()));
+static APRENDIZADO: Mutex<CriticalSectionRawMutex, RefCell<Aprendizado>> = Mutex::new(RefCell::new(Aprendizado::new()));
+
+// Distância da odometria no início da volta usando o mapa
+static INICIO_VOLTA_MM: Mutex<CriticalSectionRawMutex, Cell<f32>> = Mutex::new(Cell::new(0.0));
+
+// Setor 7 da flash do F411CE (128 KiB em 0x0806_0000) guarda o mapa da pista
+const FLASH_MAPA_OFFSET: u32 = 0x6_0000;
+const FLASH_MAPA_TAMANHO: u32 = 128 * 1024;
+// A gravação é feita em blocos de 32 bytes
+const FLASH_MAPA_BUFFER: usize = TAMANHO_MAPA_SERIALIZADO.div_ceil(32) * 32;
+
+#[derive(Clone, Copy, Debug, defmt::Format)]
+enum OperacaoFlash {
+    Salva,
+    Carrega,
+}
+
+// Pedidos para o mapa_task, que é dono da flash, e a resposta (trechos gravados/lidos)
+static OPERACAO_FLASH: Channel<CriticalSectionRawMutex, OperacaoFlash, 1> = Channel::new();
+static RESULTADO_FLASH: Signal<CriticalSectionRawMutex, Result<usize, ErroMapa>> = Signal::new();
+
+// Marcadores detectados pelo adc_task, tratados pelo voltas_task
+static MARCADORES: Channel<CriticalSectionRawMutex, Marcador, 4> = Channel::new();
+
+// Voltas completas e número de voltas até parar (0 = sem limite), ajustável com voltas=n
+static VOLTAS: Mutex<CriticalSectionRawMutex, RefCell<ContadorVoltas>> = Mutex::new(RefCell::new(ContadorVoltas::new(0)));
+
+// Parâmetros ajustáveis com get/set, na mesma ordem de DEFINICOES_PARAMETROS
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Param {
+    Kp,
+    Ki,
+    Kd,
+    VelocidadeBase,
+    VelocidadeAprendizado,
+    CalibracaoMs,
+    BrilhoLeds,
+    BrilhoCorrida,
+    Led1Ms,
+    Led2Ms,
+    Monitor,
+}
+
+const NUM_PARAMETROS: usize = Param::Monitor as usize + 1;
+
+const DEFINICOES_PARAMETROS: [Parametro; NUM_PARAMETROS] = [
+    // Erro em unidades de posição (0..POSICAO_MAX), correção em mm/s
+    Parametro::f32("kp", 0.25, 0.0, 10.0, "mm/s/pos", "ganho proporcional do PID de linha"),
+    Parametro::f32("ki", 0.0, 0.0, 100.0, "mm/s/(pos.s)", "ganho integral do PID de linha"),
+    Parametro::f32("kd", 0.0005, 0.0, 1.0, "mm/pos", "ganho derivativo do PID de linha"),
+    // Velocidade média das rodas enquanto segue a linha, somada/subtraída da correção
+    Parametro::f32("vel_base", 500.0, 0.0, VELOCIDADE_MAX_MM_S, "mm/s", "velocidade seguindo a linha sem mapa"),
+    // Baixa para a odometria não escorregar
+    Parametro::f32("vel_aprendizado", 300.0, 0.0, 1000.0, "mm/s", "velocidade da volta de aprendizado"),
+    Parametro::u32("calibracao_ms", 5000, 1000, 30000, "ms", "duração da varredura da calibração"),
+    Parametro::u32("brilho_leds", 100, 0, 100, "%", "brilho máximo dos LEDs"),
+    Parametro::u32("brilho_corrida", 15, 0, 100, "%", "brilho máximo dos LEDs seguindo a linha"),
+    Parametro::u32("led1_ms", 200, 20, 5000, "ms", "ritmo base do LED1, que indica o modo"),
+    Parametro::u32("led2_ms", 1000, 100, 5000, "ms", "período da respiração do LED2 durante a calibração"),
+    Parametro::bool("monitor", true, "log periódico do system_monitor"),
+];
+
+const NOMES_PARAMETROS: [&str; NUM_PARAMETROS] = nomes_parametros(&DEFINICOES_PARAMETROS);
+
+// Garante em compilação que cada Param aponta para a definição com o seu nome
+const _: () = {
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Kp as usize], "kp"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Ki as usize], "ki"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Kd as usize], "kd"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::VelocidadeBase as usize], "vel_base"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::VelocidadeAprendizado as usize], "vel_aprendizado"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::CalibracaoMs as usize], "calibracao_ms"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::BrilhoLeds as usize], "brilho_leds"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::BrilhoCorrida as usize], "brilho_corrida"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Led1Ms as usize], "led1_ms"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Led2Ms as usize], "led2_ms"));
+    assert!(mesmo_nome(NOMES_PARAMETROS[Param::Monitor as usize], "monitor"));
+};
+
+static PARAMETROS: Mutex<CriticalSectionRawMutex, RefCell<TabelaParametros<NUM_PARAMETROS>>> =
+    Mutex::new(RefCell::new(TabelaParametros::new(&DEFINICOES_PARAMETROS)));
+
+// Índice de cada parâmetro alterado pelo set, para as tarefas reagirem na hora.
+// Até 4 assinantes (hoje os dois led_task); um subscriber() além disso falha
+// e derruba a tarefa na inicialização, então aumente a capacidade junto.
+static ALTERACOES: PubSubChannel<CriticalSectionRawMutex, usize, 4, 4, 0> = PubSubChannel::new();
+
+fn parametro(p: Param) -> Valor {
+    PARAMETROS.lock(|t| t.borrow().valor(p as usize))
+}
+
+fn ganhos_pid() -> GanhosPid {
+    PARAMETROS.lock(|t| {
+        let t = t.borrow();
+        GanhosPid::new(
+            t.valor(Param::Kp as usize).como_f32(),
+            t.valor(Param::Ki as usize).como_f32(),
+            t.valor(Param::Kd as usize).como_f32(),
+        )
+    })
+}
+
+// Estatísticas e estado das tarefas; status e os outros comandos leem uma cópia consistente
 #[derive(Clone, Copy)]
 struct TaskStats {
-    task_count: u32,
     uptime_ms: u64,
     button_presses: u32,
     led1_blinks: u32,
     led2_blinks: u32,
     adc_samples: u32,
+    adc_overruns: u32,
     posicao: u32,
+    linha: LeituraLinha,
+    linha_perdida: u32,
+    correcao: i32,
+    ticks_esquerdo: i32,
+    ticks_direito: i32,
+    velocidade_esquerdo: VelocidadeRoda,
+    velocidade_direito: VelocidadeRoda,
+    pose: Pose,
+    distancia_mm: f32,
+    marcadores_curva: u32,
 }
 
-static mut SYSTEM_STATS: TaskStats = TaskStats {
-    task_count: 0,
+static SYSTEM_STATS: Compartilhado<TaskStats> = Compartilhado::new(TaskStats {
     uptime_ms: 0,
     button_presses: 0,
     led1_blinks: 0,
     led2_blinks: 0,
     adc_samples: 0,
-    posicao: 0,
-};
+    adc_overruns: 0,
+    posicao: POSICAO_CENTRO,
+    linha: LeituraLinha::NaLinha(POSICAO_CENTRO),
+    linha_perdida: 0,
+    correcao: 0,
+    ticks_esquerdo: 0,
+    ticks_direito: 0,
+    velocidade_esquerdo: VelocidadeRoda { ticks_s: 0, mm_s: 0.0 },
+    velocidade_direito: VelocidadeRoda { ticks_s: 0, mm_s: 0.0 },
+    pose: Pose { x: 0.0, y: 0.0, theta: 0.0 },
+    distancia_mm: 0.0,
+    marcadores_curva: 0,
+});
+
+// Tarefas criadas e tempo de CPU de cada uma, preenchido pelos ganchos de trace abaixo
+static TAREFAS: Compartilhado<RegistroTarefas> = Compartilhado::new(RegistroTarefas::new());
+
+// Ganchos chamados pelo embassy-executor com a feature "trace"
+#[no_mangle]
+fn _embassy_trace_task_new(executor_id: u32, task_id: u32) {
+    TAREFAS.atualiza(|t| t.nova(executor_id, task_id, DWT::cycle_count()));
+}
+
+#[no_mangle]
+fn _embassy_trace_task_ready_begin(_executor_id: u32, task_id: u32) {
+    TAREFAS.atualiza(|t| t.pronta(task_id, DWT::cycle_count()));
+}
+
+#[no_mangle]
+fn _embassy_trace_task_exec_begin(_executor_id: u32, task_id: u32) {
+    TAREFAS.atualiza(|t| t.inicio_poll(task_id, DWT::cycle_count()));
+}
+
+#[no_mangle]
+fn _embassy_trace_task_exec_end(_executor_id: u32, task_id: u32) {
+    TAREFAS.atualiza(|t| t.fim_poll(task_id, DWT::cycle_count()));
+}
+
+#[no_mangle]
+fn _embassy_trace_executor_idle(_executor_id: u32) {}
+
+// HSI de 16 MHz sem PLL, a configuração padrão do embassy_stm32::init
+const CICLOS_POR_US: u32 = 16;
+
+// Tarefas periódicas com prazo, medidas entre rt_inicio e rt_fim
+static RT: Compartilhado<MonitorRt> = Compartilhado::new(MonitorRt::new());
+
+// Sem vaga no monitor (MAX_TAREFAS_RT) a tarefa roda do mesmo jeito, só sem medição
+fn rt_registra(nome: &'static str, periodo_us: u64, prazo_us: u64) -> Option<usize> {
+    let ciclos = |us: u64| (us * CICLOS_POR_US as u64) as u32;
+    let id = RT.atualiza(|r| r.registra(nome, ciclos(periodo_us), ciclos(prazo_us)));
+    if id.is_none() {
+        warn!("Monitor de tempo real cheio, {} fica sem medição", nome);
+    }
+    id
+}
+
+// Chamado logo depois do await que libera a ativação, ainda no mesmo poll:
+// a liberação é quando o executor acordou a tarefa
+fn rt_inicio(id: Option<usize>) {
+    let Some(id) = id else {
+        return;
+    };
+    let agora = DWT::cycle_count();
+    let (liberacao, inicio) = TAREFAS
+        .com(|t| t.atual().map(|tarefa| (tarefa.acordou, tarefa.inicio_poll)))
+        .unwrap_or((agora, agora));
+    RT.atualiza(|r| r.inicio(id, liberacao, inicio));
+}
+
+fn rt_fim(id: Option<usize>) {
+    let Some(id) = id else {
+        return;
+    };
+    let agora = DWT::cycle_count();
+    let congelou = RT.atualiza(|r| {
+        let gravando = !r.congelado();
+        r.fim(id, agora) && gravando
+    });
+    if congelou {
+        warn!("Prazo perdido, rastro de tempo real congelado (rt trace)");
+    }
+}
+
+// Tamanho da arena de tarefas. O embassy-executor não expõe o valor, então
+// tem que mudar junto com a feature task-arena-size-32768 no Cargo.toml
+const ARENA_TAREFAS: usize = 32 * 1024;
+
+// Bytes que uma tarefa ocupa na arena: o TaskStorage com o futuro da função
+// async original, que o #[task] guarda como __<nome>_task. O SpawnToken só
+// carrega o tipo dos argumentos, por isso a função é passada ao inicia.
+trait FuncaoTarefa<Args> {
+    fn tamanho_na_arena(&self) -> usize;
+}
+
+macro_rules! funcao_tarefa {
+    ($($arg:ident),*) => {
+        impl<T, F: Future + 'static, $($arg),*> FuncaoTarefa<($($arg,)*)> for T
+        where
+            T: Fn($($arg),*) -> F,
+        {
+            fn tamanho_na_arena(&self) -> usize {
+                core::mem::size_of::<TaskStorage<F>>()
+            }
+        }
+    };
+}
+
+funcao_tarefa!();
+funcao_tarefa!(A);
+funcao_tarefa!(A, B);
+funcao_tarefa!(A, B, C);
+// adc_task recebe os periféricos um a um
+funcao_tarefa!(A, B, C, D, E, G, H, I, J, K, L, M, N);
+
+// Símbolos do link.x do cortex-m-rt
+extern "C" {
+    static mut __sheap: u32;
+    static _stack_start: u32;
+    static __sdata: u32;
+    static __edata: u32;
+    static __sbss: u32;
+    static __ebss: u32;
+}
+
+// Região entre o fim do .bss e o topo da RAM, usada só pela pilha
+fn regiao_pilha() -> (*mut u32, *mut u32) {
+    unsafe { (addr_of_mut!(__sheap), addr_of!(_stack_start) as *mut u32) }
+}
+
+// Pinta a pilha ainda não usada, com uma folga abaixo do ponteiro atual para esta função
+#[inline(never)]
+fn pinta_pilha() {
+    let (inicio, _) = regiao_pilha();
+    let sp = cortex_m::register::msp::read() as usize - 64;
+    unsafe { memoria::pinta(inicio, sp as *mut u32) };
+}
+
+// Com a feature heap o alocador global fica num bloco fixo do .bss, abaixo da pilha
+#[cfg(feature = "heap")]
+const TAMANHO_HEAP: usize = 8 * 1024;
 
-static mut LEDSPEED: u32 = 0;
+#[cfg(feature = "heap")]
+#[global_allocator]
+static HEAP: Alocador = Alocador::new();
+
+#[cfg(feature = "heap")]
+fn inicia_heap() {
+    static mut MEMORIA_HEAP: [core::mem::MaybeUninit<u8>; TAMANHO_HEAP] = [core::mem::MaybeUninit::uninit(); TAMANHO_HEAP];
+    // Chamada uma vez só, no main, então a referência é exclusiva
+    HEAP.inicia(unsafe { &mut *addr_of_mut!(MEMORIA_HEAP) });
+}
+
+// Sensores e controle rodam na interrupção do USART2, livre porque PA2/PA3 são
+// entradas do ADC; o console e o monitor ficam no executor do thread mode
+static EXECUTOR_ALTA: InterruptExecutor = InterruptExecutor::new();
+static EXECUTOR_BAIXA: StaticCell<Executor> = StaticCell::new();
+
+#[interrupt]
+unsafe fn USART2() {
+    EXECUTOR_ALTA.on_interrupt()
+}
+
+// Cria a tarefa e dá nome a ela no registro
+fn inicia<S, A>(spawner: &Spawner, nome: &'static str, tarefa: SpawnToken<S>, funcao: impl FuncaoTarefa<A>) {
+    spawner.spawn(tarefa).unwrap();
+    TAREFAS.atualiza(|t| t.nomeia_ultima(nome, "thread", funcao.tamanho_na_arena()));
+}
+
+// O mesmo no executor de alta prioridade; a tarefa pode começar a rodar antes
+// do nome ser gravado, mas o gancho de tarefa nova já foi chamado no spawn
+fn inicia_alta<S: Send, A>(spawner: &SendSpawner, nome: &'static str, tarefa: SpawnToken<S>, funcao: impl FuncaoTarefa<A>) {
+    spawner.spawn(tarefa).unwrap();
+    TAREFAS.atualiza(|t| t.nomeia_ultima(nome, "alta", funcao.tamanho_na_arena()));
+}
 
 bind_interrupts!(struct Irqs {
     USART1 => embassy_stm32::usart::InterruptHandler<peripherals::USART1>;
@@ -49,109 +535,230 @@ bind_interrupts!(struct Irqs {
 #[embassy_executor::task]
 async fn console_shell(mut uart: Uart<'static, embassy_stm32::mode::Async>) {
     info!("Console_Shell iniciado");
-    
+
     let mut buffer = [0u8; 1];
-    let mut _cmd_buffer = String::<64>::new();
+    let mut editor = EditorLinha::new();
+    let mut tab_anterior = false;
     loop {
-        let _ = uart.read(&mut buffer).await; 
-        if buffer[0] == b'\r' || buffer[0] == b'\n' {
-            if !_cmd_buffer.is_empty() {
-                process_command(&mut uart, &_cmd_buffer).await;
-                _cmd_buffer.clear();
-            }
-            let _ = uart.write(b"\r\n> ").await; // Prompt
-        } else if buffer[0] == b'\x08' || buffer[0] == 127 { // Backspace
-            if !_cmd_buffer.is_empty() {
-                _cmd_buffer.pop();
-                let _ = uart.write(b"\x08 \x08").await; // Apaga o último caractere
+        if uart.read(&mut buffer).await.is_err() {
+            continue;
+        }
+        let evento = editor.processa(buffer[0]);
+        if !editor.saida().is_empty() {
+            let _ = uart.write(editor.saida()).await; // Eco e movimentos do cursor
+        }
+        let tab = evento == Some(EventoLinha::Tab);
+        match evento {
+            Some(EventoLinha::Tab) => completa(&mut uart, &mut editor, tab_anterior).await,
+            Some(EventoLinha::Pronta) => {
+                if !editor.linha().is_empty() {
+                    process_command(&mut uart, editor.linha()).await;
+                }
+                let _ = uart.write(b"\r\n> ").await; // Prompt
             }
-        } else {
-            if _cmd_buffer.push(buffer[0] as char).is_ok() {
-                let _ = uart.write(&buffer).await; // Ecoa o caractere
+            Some(EventoLinha::Cancelada) => {
+                let _ = uart.write(b"> ").await;
             }
+            None => {}
+        }
+        tab_anterior = tab;
+    }
+}
+
+// Primeiro TAB completa até onde os candidatos concordam; o segundo lista os candidatos
+async fn completa(uart: &mut Uart<'static, embassy_stm32::mode::Async>, editor: &mut EditorLinha, tab_anterior: bool) {
+    let (parcial, candidatos) = candidatos(editor.ate_cursor(), Comandos::COMANDOS);
+    let comum = prefixo_comum(&candidatos);
+
+    if comum.len() > parcial.len() || candidatos.len() == 1 {
+        editor.insere_texto(&comum[parcial.len()..]);
+        let _ = uart.write(editor.saida()).await;
+        if candidatos.len() == 1 {
+            // Palavra completa: já separa o próximo argumento
+            editor.insere_texto(" ");
+            let _ = uart.write(editor.saida()).await;
         }
+    } else if tab_anterior && !candidatos.is_empty() {
+        let _ = uart.write(b"\r\n").await;
+        for c in &candidatos {
+            escreve(uart, format_args!("{}  ", c)).await;
+        }
+        let _ = uart.write(b"\r\n> ").await;
+        editor.redesenha();
+        let _ = uart.write(editor.saida()).await;
+    } else {
+        let _ = uart.write(b"\x07").await; // Sem o que completar: campainha
     }
 }
 
+registro_comandos!(Comandos {
+    sistema::Status, sistema::Reset, sistema::ComandoModo, sistema::Tasks, sistema::Rt, sistema::Mem, sistema::Heap,
+    parametros::Params, parametros::Get, parametros::Set,
+    sensores::Calibrar, sensores::ComandoCalibracao, sensores::CalibracaoLimpa,
+    controle::ComandoPid, controle::Seguir, controle::Parar,
+    motores::Motor, motores::Vel,
+    odometria::Encoder, odometria::ComandoPose, odometria::PoseZera,
+    mapa::Mapa, mapa::Voltas,
+});
 
 async fn process_command(uart: &mut Uart<'static, embassy_stm32::mode::Async>, cmd: &str) {
-    let mut response = String::<512>::new();
     info!("Mensagem: {}", cmd);
-    
-    if cmd.starts_with("led1=") {
-        if let Ok(valor) = cmd[5..].trim().parse::<u32>() {
-            unsafe { LEDSPEED = valor; }
-            let _ = core::fmt::write(&mut response, format_args!("Velocidade do LED1 ajustada para {} ms\r\n", valor));
-        } else {
-            let _ = core::fmt::write(&mut response, format_args!("Valor inválido!\r\n"));
-        }
-    }else{
-            match cmd.trim() {
-            "help" => {
-            let _ = core::fmt::write(&mut response, format_args!(
-                "\n=== Comandos do Sistema ===\n\
-                 status\n\r\
-                 reset\n\r\
-                 help\n\r\
-                 led1=n (n velocidade desejada em ms)\n\r"
-            ));
-            }
-            "status" => {
-                let stats = unsafe { SYSTEM_STATS };
-                let _ = core::fmt::write(&mut response, format_args!(
-                "\n=== Status do Sistema ===\r\n\
-                 Uptime: {} ms\r\n\
-                 Tarefas ativas: {}\r\n\
-                 Botão pressionado: {} vezes\r\n\
-                 LED1 piscou: {} vezes\r\n\
-                 LED2 piscou: {} vezes\r\n\
-                 ADC Samples: {} vezes\r\n\
-                 Posição do peso: {}\r\n",
-                stats.uptime_ms, stats.task_count, 
-                stats.button_presses, stats.led1_blinks, stats.led2_blinks,
-                stats.adc_samples, stats.posicao
-            ));
-            }
-            "reset" => {
-                unsafe {
-                    SYSTEM_STATS.button_presses = 0;
-                    SYSTEM_STATS.led1_blinks = 0;
-                    SYSTEM_STATS.led2_blinks = 0;
-                }
-                let _ = core::fmt::write(&mut response, format_args!("\nEstatísticas resetadas!\r\n"));
-            }
-        
-            _ => {
-                let _ = core::fmt::write(&mut response, format_args!("Comando não encontrado. Digite 'help' para ajuda.\r\n"));
+
+    match Comandos::executa(cmd, uart).await {
+        Ok(()) => {}
+        Err(ErroComando::Desconhecido) => {
+            escreve(uart, format_args!("Comando não encontrado. Digite 'help' para ajuda.\r\n")).await;
+        }
+        Err(ErroComando::ArgumentoInvalido) => {
+            let (nome, _) = divide_comando(cmd);
+            if let Some(c) = Comandos::procura(nome) {
+                escreve(uart, format_args!("Valor inválido! Uso: {} {}\r\n", c.nome, c.argumentos)).await;
             }
         }
+    }
+}
+
+// Taxa de varredura do array de sensores, disparada pelo TIM1
+const TAXA_AMOSTRAGEM_HZ: u32 = 1000;
 
+// Liga o seguidor e a malha de velocidade, zerando a contagem de voltas
+fn liga_seguidor() {
+    VOLTAS.lock(|v| v.borrow_mut().reinicia());
+    SEGUINDO.store(true, Ordering::Relaxed);
+    MALHA_VELOCIDADE.store(true, Ordering::Relaxed);
+}
+
+// Saindo de Seguindo o modo de pista volta ao livre. Uma volta de aprendizado
+// interrompida (para, falha, troca de modo) é descartada, senão o encoder_task
+// continuaria gravando com o robô parado ou carregado na mão.
+async fn para_seguidor() {
+    SEGUINDO.store(false, Ordering::Relaxed);
+    if MODO_PISTA.swap(PISTA_LIVRE, Ordering::Relaxed) == PISTA_APRENDENDO {
+        APRENDIZADO.lock(|a| a.borrow_mut().reinicia());
+        warn!("Volta de aprendizado interrompida, descartada");
     }
-    
-    let _ = uart.write(response.as_bytes()).await;
-    let _ = uart.write(b"\r\n> ").await;
+    MALHA_VELOCIDADE.store(false, Ordering::Relaxed);
+    ponte::para(Parada::Freio).await;
 }
 
-const PESOS: [u32; 8] = [0, 1000, 2000, 3000, 4000, 5000, 6000, 7000];
+// Modo de operação; só muda por muda_modo e falha, que fazem as ações de cada modo
+static MODO: Compartilhado<MaquinaModo> = Compartilhado::new(MaquinaModo::new());
 
-fn calcula_posicao_peso(sensores: &[u16; 8]) -> u32 {
-    let mut soma_pesos = 0u32;
-    let mut soma_valores = 0u32;
+// Tempo seguindo sem ver a linha até parar com falha
+const LINHA_PERDIDA_MS: u64 = 500;
+
+fn modo_atual() -> Modo {
+    MODO.com(|m| m.modo())
+}
 
-    for (i, &valor) in sensores.iter().enumerate() {
-        soma_pesos += valor as u32 * PESOS[i];
-        soma_valores += valor as u32;
+async fn muda_modo(destino: Modo) -> Result<(), TransicaoInvalida> {
+    let anterior = MODO.atualiza(|m| m.pede(destino, Instant::now().as_millis()))?;
+    if anterior != destino {
+        aplica_modo(anterior, destino).await;
     }
-    if soma_valores == 0 {
-        0
-    } else {
-        soma_pesos / soma_valores
+    Ok(())
+}
+
+// Falhas levantadas no executor de alta prioridade, tratadas pelo falha_task
+static FALHA: Signal<CriticalSectionRawMutex, CodigoFalha> = Signal::new();
+
+async fn falha(codigo: CodigoFalha) {
+    let Some(anterior) = MODO.atualiza(|m| m.registra_falha(codigo, Instant::now().as_millis())) else {
+        return;
+    };
+    error!("Falha: {:?}", codigo);
+    aplica_modo(anterior, Modo::Falha).await;
+}
+
+// Ações de saída e entrada: os motores só ficam ligados em Seguindo
+async fn aplica_modo(anterior: Modo, novo: Modo) {
+    info!("Modo: {} -> {}", anterior.nome(), novo.nome());
+    mostra_padrao(Led::Estado, padrao_modo(novo));
+    // O limite de brilho muda com Seguindo; o LED de calibração só vê isso com um padrão novo
+    if (anterior == Modo::Seguindo) != (novo == Modo::Seguindo) {
+        mostra_padrao(Led::Calibracao, Led::Calibracao.padrao_atual());
+    }
+    if anterior == Modo::Calibrando {
+        CANCELA_CALIBRACAO.signal(());
+    }
+    // Uma falha sinalizada pouco antes de o usuário sair de Falha voltaria a ela na hora
+    if anterior == Modo::Falha {
+        FALHA.reset();
+    }
+    match novo {
+        Modo::Seguindo => liga_seguidor(),
+        _ => para_seguidor().await,
     }
+    if novo == Modo::Calibrando {
+        CALIBRA_SIGNAL.signal(());
+    }
+}
+
+// O modo de pista é escolhido antes de pedir Seguindo, então confere a transição primeiro
+fn pode_seguir() -> bool {
+    let modo = modo_atual();
+    modo == Modo::Seguindo || modo.pode_ir(Modo::Seguindo)
+}
+
+// Teste de bancada dos motores só no modo Bancada; sair dele para os motores
+fn bancada_liberada() -> bool {
+    modo_atual() == Modo::Bancada
+}
+
+// Clique avança o modo, clique duplo para e aperto longo calibra. O clique só
+// chega depois da janela do duplo, então o primeiro aperto de um duplo não arma
+#[embassy_executor::task]
+async fn modo_botao() {
+    let mut eventos = BOTAO.subscriber().unwrap();
+    loop {
+        let destino = match eventos.next_message_pure().await {
+            EventoBotao::Clique => modo_atual().seguinte(),
+            EventoBotao::CliqueDuplo => Some(Modo::Parado),
+            EventoBotao::ApertoLongo(_) => Some(Modo::Calibrando),
+            EventoBotao::Aperto | EventoBotao::Soltura => None,
+        };
+        if let Some(destino) = destino {
+            if let Err(e) = muda_modo(destino).await {
+                warn!("Botão: modo {} não vai para {}", e.de.nome(), e.para.nome());
+            }
+        }
+    }
+}
+
+// Fecha a volta de aprendizado e troca o mapa atual; devolve (trechos, comprimento)
+fn finaliza_aprendizado() -> (usize, f32) {
+    MODO_PISTA.store(PISTA_LIVRE, Ordering::Relaxed);
+    let mapa = APRENDIZADO.lock(|a| a.borrow_mut().finaliza());
+    let resumo = (mapa.trechos().len(), mapa.comprimento_mm());
+    MAPA.lock(|m| *m.borrow_mut() = mapa);
+    resumo
+}
+
+// Uma conversão da sequência inteira a cada borda de subida do TIM1_CC1.
+// O start() do RingBufferedAdc liga o modo contínuo e dispara por software;
+// com o gatilho já escolhido antes, só falta desligar CONT logo depois. O ADC
+// então para no fim da sequência em andamento e o DMA fica num quadro inteiro.
+fn inicia_adc(adc: &mut RingBufferedAdc<peripherals::ADC1>) {
+    let cr2 = embassy_stm32::pac::ADC1.cr2();
+    cr2.modify(|w| {
+        w.set_exten(Exten::RISINGEDGE);
+        w.set_extsel(0); // TIM1_CC1
+    });
+    let _ = adc.start();
+    cr2.modify(|w| {
+        w.set_cont(false);
+        w.set_exten(Exten::RISINGEDGE);
+        w.set_extsel(0);
+    });
 }
 
 #[embassy_executor::task]
 async fn adc_task(
     mut adc: Adc<'static, peripherals::ADC1>,
+    dma: peripherals::DMA2_CH0,
+    mut gatilho: SimplePwmChannel<'static, peripherals::TIM1>,
+    marcador_esquerdo: Input<'static>,
+    marcador_direito: Input<'static>,
     mut pin0: peripherals::PA0,
     mut pin1: peripherals::PA1,
     mut pin2: peripherals::PA2,
@@ -162,66 +769,411 @@ async fn adc_task(
     mut pin7: peripherals::PA7,
 ) {
     adc.set_resolution(Resolution::BITS12);
-    adc.set_sample_time(SampleTime::CYCLES3);
+
+    // O DMA escreve dois quadros de 8 canais; cada read() espera meio buffer
+    let adc_data: &mut [u16; 2 * NUM_SENSORES] =
+        singleton!(ADCDAT : [u16; 2 * NUM_SENSORES] = [0u16; 2 * NUM_SENSORES]).unwrap();
+    let mut adc: RingBufferedAdc<peripherals::ADC1> = adc.into_ring_buffered(dma, adc_data);
+
+    adc.set_sample_sequence(Sequence::One, &mut pin0, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Two, &mut pin1, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Three, &mut pin2, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Four, &mut pin3, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Five, &mut pin4, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Six, &mut pin5, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Seven, &mut pin6, SampleTime::CYCLES3);
+    adc.set_sample_sequence(Sequence::Eight, &mut pin7, SampleTime::CYCLES3);
+
+    // O canal 1 do TIM1 só gera o evento de comparação, sem pino de saída
+    gatilho.set_duty_cycle_fraction(1, 2);
+    gatilho.enable();
+
+    inicia_adc(&mut adc);
+
+    let mut estimador = EstimadorPosicao::default();
+    let mut detector = DetectorMarcadores::default();
+    let mut samples = [0u16; NUM_SENSORES];
+    let periodo_us = 1_000_000 / TAXA_AMOSTRAGEM_HZ as u64;
+    let rt = rt_registra("adc_task", periodo_us, periodo_us);
 
     loop {
-        let mut samples = [0u16; 8];
-        samples[0] = adc.blocking_read(&mut pin0);
-        samples[1] = adc.blocking_read(&mut pin1);
-        samples[2] = adc.blocking_read(&mut pin2);
-        samples[3] = adc.blocking_read(&mut pin3);
-        samples[4] = adc.blocking_read(&mut pin4);
-        samples[5] = adc.blocking_read(&mut pin5);
-        samples[6] = adc.blocking_read(&mut pin6);
-        samples[7] = adc.blocking_read(&mut pin7);
+        if let Err(e) = adc.read(&mut samples).await {
+            // Com overrun a posição de cada canal no buffer se perde: reinicia o DMA
+            warn!("ADC overrun: {:?}", e);
+            SYSTEM_STATS.atualiza(|s| s.adc_overruns += 1);
+            inicia_adc(&mut adc);
+            continue;
+        }
+        rt_inicio(rt);
+
+        // Durante a varredura só registra min/max; depois normaliza se a calibração for válida
+        let normalizado = CALIBRACAO.lock(|c| {
+            let mut cal = c.borrow_mut();
+            if CALIBRANDO.load(Ordering::Relaxed) {
+                cal.registra(&samples);
+                escala_bruta(&samples)
+            } else if cal.valida() {
+                cal.normaliza(&samples)
+            } else {
+                escala_bruta(&samples)
+            }
+        });
 
-        let pos = calcula_posicao_peso(&samples);
+        let leitura = estimador.atualiza(&normalizado);
 
-        unsafe {
-            SYSTEM_STATS.adc_samples += 1;
-            SYSTEM_STATS.posicao = pos;
+        // Os sensores de marcador puxam a saída para baixo sobre a faixa branca
+        if let Some(marcador) = detector.atualiza(marcador_esquerdo.is_low(), marcador_direito.is_low()) {
+            let _ = MARCADORES.try_send(marcador);
         }
 
-        Timer::after_micros(100).await;
+        SYSTEM_STATS.atualiza(|s| {
+            // Conta só a transição de "na linha" para "perdida"
+            if s.linha.na_linha() && !leitura.na_linha() {
+                s.linha_perdida += 1;
+            }
+            s.adc_samples += 1;
+            s.posicao = leitura.posicao();
+            s.linha = leitura;
+        });
+        rt_fim(rt);
+    }
+}
+
+// Velocidade média das rodas conforme o modo de pista
+fn velocidade_base() -> f32 {
+    match MODO_PISTA.load(Ordering::Relaxed) {
+        PISTA_APRENDENDO => parametro(Param::VelocidadeAprendizado).como_f32(),
+        PISTA_MAPA => {
+            let distancia = SYSTEM_STATS.com(|s| s.distancia_mm) - INICIO_VOLTA_MM.lock(|i| i.get());
+            MAPA.lock(|m| PERFIL_PISTA.velocidade_em(&m.borrow(), distancia))
+        }
+        _ => parametro(Param::VelocidadeBase).como_f32(),
     }
 }
 
 #[embassy_executor::task]
-async fn blink_fast(mut led: Output<'static>) {
+async fn voltas_task() {
     loop {
-        led.set_high();
-        Timer::after_millis(200).await;
-        led.set_low();
-        Timer::after_millis(200).await;
-        unsafe {
-            SYSTEM_STATS.led1_blinks += 1;
+        let marcador = MARCADORES.receive().await;
+        info!("Marcador: {:?}", marcador);
+
+        // Com o robô parado (ou carregado na mão) os marcadores não contam
+        if !SEGUINDO.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        match marcador {
+            Marcador::Partida => {
+                let evento = VOLTAS.lock(|v| v.borrow_mut().passou_partida());
+                info!("Volta: {:?}", evento);
+
+                // A partida ressincroniza a distância usada pelo mapa
+                let distancia = SYSTEM_STATS.com(|s| s.distancia_mm);
+                match evento {
+                    EventoVolta::Largada => {
+                        if MODO_PISTA.load(Ordering::Relaxed) == PISTA_APRENDENDO {
+                            APRENDIZADO.lock(|a| a.borrow_mut().reinicia());
+                        }
+                        INICIO_VOLTA_MM.lock(|i| i.set(distancia));
+                    }
+                    EventoVolta::Volta(_) | EventoVolta::Fim(_) => {
+                        // A primeira volta completa fecha o aprendizado
+                        if MODO_PISTA.load(Ordering::Relaxed) == PISTA_APRENDENDO {
+                            let (trechos, comprimento) = finaliza_aprendizado();
+                            info!("Pista aprendida: {} trechos, {} mm", trechos, comprimento);
+                        }
+                        INICIO_VOLTA_MM.lock(|i| i.set(distancia));
+                    }
+                }
+                if let EventoVolta::Fim(voltas) = evento {
+                    info!("{} voltas completas, parando", voltas);
+                    let _ = muda_modo(Modo::Parado).await;
+                }
+            }
+            Marcador::Curva => SYSTEM_STATS.atualiza(|s| s.marcadores_curva += 1),
+            Marcador::Cruzamento => {}
         }
     }
 }
 
+
 #[embassy_executor::task]
-async fn blink_slow(mut led: Output<'static>) {
+async fn controle_task() {
+    let periodo = Duration::from_micros(CONTROLE_PERIODO_US);
+    let dt = CONTROLE_PERIODO_US as f32 / 1_000_000.0;
+    let mut pid = Pid::new(ganhos_pid(), 1000.0, 300.0, 0.7);
+    let mut ticker = Ticker::every(periodo);
+    let rt = rt_registra("controle_task", CONTROLE_PERIODO_US, CONTROLE_PERIODO_US);
+
+    let mut seguia = false;
+    let mut linha_vista = Instant::now();
+
     loop {
-        led.set_high();
-        Timer::after_millis(1000).await;
-        led.set_low();
-        Timer::after_millis(1000).await;
-        unsafe {
-            SYSTEM_STATS.led2_blinks += 1;
+        ticker.next().await;
+        rt_inicio(rt);
+
+        let seguindo = SEGUINDO.load(Ordering::Relaxed);
+        if seguindo && !seguia {
+            pid.reset();
+        }
+        seguia = seguindo;
+
+        pid.define_ganhos(ganhos_pid());
+        let (posicao, na_linha) = SYSTEM_STATS.com(|s| (s.posicao, s.linha.na_linha()));
+        let erro = erro_posicao(posicao);
+        let correcao = pid.atualiza(erro, dt);
+        if na_linha || !seguindo {
+            linha_vista = Instant::now();
+        }
+
+        SYSTEM_STATS.atualiza(|s| s.correcao = correcao as i32);
+
+        // Linha à direita (erro positivo) acelera a roda esquerda para virar à direita.
+        // A correção é em mm/s; a malha de velocidade converte em duty.
+        // Base mais correção passa do máximo, então o alvo é limitado como no comando vel.
+        if seguindo {
+            let base = velocidade_base();
+            let alvo = |v: f32| limita_alvo(v).unwrap_or(0.0);
+            ALVO_VELOCIDADE.lock(|a| a.set((alvo(base + correcao), alvo(base - correcao))));
+        }
+        rt_fim(rt);
+
+        // As ações de saída esperam a ponte H, então ficam fora do laço de controle
+        if seguindo && linha_vista.elapsed() > Duration::from_millis(LINHA_PERDIDA_MS) {
+            FALHA.signal(CodigoFalha::LinhaPerdida);
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn encoder_task(
+    esquerdo: Qei<'static, peripherals::TIM2>,
+    direito: Qei<'static, peripherals::TIM4>,
+) {
+    let geometria = GeometriaRoda::default();
+    let periodo_s = ENCODER_PERIODO_MS as f32 / 1000.0;
+    // O motor direito é montado espelhado, então o encoder dele conta ao contrário
+    let mut contador_esquerdo = ContadorEstendido::new(false);
+    let mut contador_direito = ContadorEstendido::new(true);
+    let mut odometria = Odometria::default();
+    let mut controle_esquerdo = ControleVelocidade::new(ParametrosVelocidade::padrao());
+    let mut controle_direito = ControleVelocidade::new(ParametrosVelocidade::padrao());
+    let mut malha_ativa = false;
+    let mut ticker = Ticker::every(Duration::from_millis(ENCODER_PERIODO_MS));
+    let rt = rt_registra("encoder_task", ENCODER_PERIODO_MS * 1000, ENCODER_PERIODO_MS * 1000);
+
+    loop {
+        ticker.next().await;
+        rt_inicio(rt);
+
+        let delta_esquerdo = contador_esquerdo.atualiza(esquerdo.count());
+        let delta_direito = contador_direito.atualiza(direito.count());
+        let velocidade_esquerdo = calcula_velocidade(delta_esquerdo, periodo_s, &geometria);
+        let velocidade_direito = calcula_velocidade(delta_direito, periodo_s, &geometria);
+
+        if ZERA_POSE.swap(false, Ordering::Relaxed) {
+            odometria.zera();
+        }
+        if MODO_PISTA.load(Ordering::Relaxed) == PISTA_APRENDENDO {
+            let (deslocamento, giro) = odometria.incremento(delta_esquerdo, delta_direito);
+            let correcao = SYSTEM_STATS.com(|s| s.correcao) as f32;
+            if let Err(e) = APRENDIZADO.lock(|a| a.borrow_mut().registra(deslocamento, giro, correcao)) {
+                warn!("Aprendizado da pista: {:?}", e);
+            }
+        }
+        let pose = odometria.atualiza(delta_esquerdo, delta_direito);
+
+        SYSTEM_STATS.atualiza(|s| {
+            s.pose = pose;
+            s.distancia_mm = odometria.distancia_mm();
+            s.ticks_esquerdo = contador_esquerdo.total();
+            s.ticks_direito = contador_direito.total();
+            s.velocidade_esquerdo = velocidade_esquerdo;
+            s.velocidade_direito = velocidade_direito;
+        });
+
+        // A malha de velocidade roda no mesmo período da amostragem dos encoders
+        // Quem desliga a malha (parar, motor) é quem decide como parar os motores
+        let malha = MALHA_VELOCIDADE.load(Ordering::Relaxed);
+        if malha != malha_ativa {
+            controle_esquerdo.reset();
+            controle_direito.reset();
+            malha_ativa = malha;
+        }
+        if malha {
+            let (alvo_esquerdo, alvo_direito) = ALVO_VELOCIDADE.lock(|a| a.get());
+            ponte::aciona(
+                controle_esquerdo.atualiza(alvo_esquerdo, velocidade_esquerdo.mm_s, periodo_s),
+                controle_direito.atualiza(alvo_direito, velocidade_direito.mm_s, periodo_s),
+            ).await;
+        }
+        rt_fim(rt);
+    }
+}
+
+// Grava e lê o mapa da pista na flash. Apagar o setor bloqueia o núcleo por
+// cerca de 1 s, então só deve ser pedido com o robô parado.
+#[embassy_executor::task]
+async fn mapa_task(mut flash: Flash<'static, Blocking>) {
+    let mut buf = [0xFFu8; FLASH_MAPA_BUFFER];
+    let mut operacao = OperacaoFlash::Carrega;
+
+    // Na partida carrega o mapa salvo, se houver
+    loop {
+        let resultado = match operacao {
+            OperacaoFlash::Salva => {
+                buf.fill(0xFF);
+                match MAPA.lock(|m| m.borrow().serializa(&mut buf)) {
+                    Ok(tamanho) => {
+                        // Motores freados antes do núcleo travar, caso o modo tenha mudado depois do comando
+                        ponte::para(Parada::Freio).await;
+                        // O resto do bloco fica como apagado (0xFF)
+                        let tamanho = tamanho.div_ceil(32) * 32;
+                        let gravou = flash.blocking_erase(FLASH_MAPA_OFFSET, FLASH_MAPA_OFFSET + FLASH_MAPA_TAMANHO).is_ok()
+                            && flash.blocking_write(FLASH_MAPA_OFFSET, &buf[..tamanho]).is_ok();
+                        if gravou {
+                            Ok(MAPA.lock(|m| m.borrow().trechos().len()))
+                        } else {
+                            Err(ErroMapa::Corrompido)
+                        }
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            OperacaoFlash::Carrega => {
+                if flash.blocking_read(FLASH_MAPA_OFFSET, &mut buf).is_err() {
+                    Err(ErroMapa::Corrompido)
+                } else {
+                    MapaPista::desserializa(&buf).map(|mapa| {
+                        let trechos = mapa.trechos().len();
+                        MAPA.lock(|m| *m.borrow_mut() = mapa);
+                        trechos
+                    })
+                }
+            }
+        };
+        info!("Mapa {:?}: {:?}", operacao, resultado);
+        RESULTADO_FLASH.signal(resultado);
+
+        operacao = OPERACAO_FLASH.receive().await;
+    }
+}
+
+#[embassy_executor::task]
+async fn motores_task(mut motores: Motores) {
+    info!("Motores prontos");
+    motores.executa().await;
+}
+
+// Tarefa de um LED: toca o padrão atual e troca quando chega outro pelo sinal
+#[embassy_executor::task(pool_size = 2)]
+async fn led_task(mut saida: SaidaLed, led: Led, inicial: Padrao) {
+    let mut tocador = Tocador::new(inicial);
+    let mut alteracoes = ALTERACOES.subscriber().unwrap();
+    let mut aceso = false;
+    loop {
+        let passo = tocador.proximo();
+        saida.define(ciclo_ativo(passo.brilho, limite_brilho()));
+        // Conta só as piscadas (acender um LED apagado), não o LED aceso direto
+        if passo.brilho > 0 && !aceso && passo.duracao_ms.is_some() {
+            SYSTEM_STATS.atualiza(|s| match led {
+                Led::Estado => s.led1_blinks += 1,
+                Led::Calibracao => s.led2_blinks += 1,
+            });
+        }
+        aceso = passo.brilho > 0;
+
+        let fim = async {
+            match passo.duracao_ms {
+                Some(ms) => Timer::after_millis(ms as u64).await,
+                None => pending().await,
+            }
+        };
+        // true quando mudou o ritmo do LED, false quando mudou um limite de brilho
+        let alteracao = async {
+            loop {
+                let i = alteracoes.next_message_pure().await;
+                if i == led.ritmo() as usize {
+                    break true;
+                }
+                if i == Param::BrilhoLeds as usize || i == Param::BrilhoCorrida as usize {
+                    break false;
+                }
+            }
+        };
+        match select3(fim, led.sinal().wait(), alteracao).await {
+            Either3::First(()) => {}
+            Either3::Second(padrao) => tocador.troca(padrao),
+            Either3::Third(true) => tocador.troca(led.padrao_atual()),
+            // Recomeça o padrão atual já com o limite novo
+            Either3::Third(false) => tocador.troca(tocador.padrao()),
         }
     }
 }
 
 #[embassy_executor::task]
 async fn button_handler(mut button: ExtiInput<'static>) {
+    let mut botao = Botao::new();
+    let publicador = BOTAO.immediate_publisher();
     loop {
-        button.wait_for_rising_edge().await;
-        info!("Botão pressionado!");
-        unsafe {
-            SYSTEM_STATS.button_presses += 1;
+        // Solto, só acorda na próxima borda; durante um gesto amostra para medir os tempos
+        if botao.ocioso() {
+            button.wait_for_any_edge().await;
+        } else {
+            Timer::after_millis(BOTAO_PERIODO_MS).await;
         }
-        BUTTON_SIGNAL.signal(());
-        Timer::after_millis(100).await; 
+        let Some(evento) = botao.atualiza(button.is_high(), Instant::now().as_millis() as u32) else {
+            continue;
+        };
+        info!("Botão: {}", evento);
+        if matches!(evento, EventoBotao::Aperto | EventoBotao::CliqueDuplo) {
+            SYSTEM_STATS.atualiza(|s| s.button_presses += 1);
+        }
+        publicador.publish_immediate(evento);
+    }
+}
+
+
+#[embassy_executor::task]
+async fn calibracao_task() {
+    loop {
+        // Pedida pelo modo Calibrando (console, aperto longo do botão ou comando mode)
+        CALIBRA_SIGNAL.wait().await;
+        CANCELA_CALIBRACAO.reset();
+        // Se o modo saiu de Calibrando antes daqui, o cancelamento acabou de ser apagado
+        if modo_atual() != Modo::Calibrando {
+            continue;
+        }
+        info!("Calibração iniciada");
+
+        CALIBRACAO.lock(|c| c.borrow_mut().limpa());
+        CALIBRANDO.store(true, Ordering::Relaxed);
+        mostra_padrao(Led::Calibracao, padrao_varredura());
+        let duracao = Timer::after_millis(parametro(Param::CalibracaoMs).como_u32() as u64);
+        let cancelada = matches!(select(duracao, CANCELA_CALIBRACAO.wait()).await, Either::Second(_));
+        CALIBRANDO.store(false, Ordering::Relaxed);
+        let cal = CALIBRACAO.lock(|c| *c.borrow());
+        mostra_padrao(Led::Calibracao, padrao_calibracao(&cal));
+        if cancelada {
+            warn!("Calibração interrompida");
+            continue;
+        }
+
+        if cal.valida() {
+            info!("Calibração concluída: min {} max {}", cal.min, cal.max);
+            let _ = muda_modo(Modo::Ocioso).await;
+        } else {
+            warn!("Calibração inválida, algum sensor não viu a linha: min {} max {}", cal.min, cal.max);
+            falha(CodigoFalha::CalibracaoInvalida).await;
+        }
+    }
+}
+
+// Faz a transição para Falha pedida pelas tarefas que não podem esperar por ela
+#[embassy_executor::task]
+async fn falha_task() {
+    loop {
+        falha(FALHA.wait().await).await;
     }
 }
 
@@ -231,30 +1183,60 @@ async fn system_monitor() {
     loop {
         Timer::after_secs(1).await;
         info!("------------------------------");
-        unsafe {
-            SYSTEM_STATS.uptime_ms = start_time.elapsed().as_millis();
-            SYSTEM_STATS.task_count = 4; 
+        let stats = SYSTEM_STATS.atualiza(|s| {
+            s.uptime_ms = start_time.elapsed().as_millis();
+            *s
+        });
+        if !parametro(Param::Monitor).como_bool() {
+            continue;
         }
-        let uptime_s = unsafe { SYSTEM_STATS.uptime_ms / 1000 };
+        let uptime_s = stats.uptime_ms / 1000;
         info!("Monitor: Sistema ativo há {}s, {} amostras ADC", 
               uptime_s,
-              unsafe { SYSTEM_STATS.adc_samples });
+              stats.adc_samples);
         info!("Performance: {} amostras/s, {} botão, LEDs: {}/{}, Posicao: {}", 
-              if uptime_s > 0 { unsafe { SYSTEM_STATS.adc_samples as u64 * 1000 / SYSTEM_STATS.uptime_ms } } else { 0 },
-              unsafe { SYSTEM_STATS.button_presses },
-              unsafe { SYSTEM_STATS.led1_blinks },
-              unsafe { SYSTEM_STATS.led2_blinks },
-              unsafe { SYSTEM_STATS.posicao });
+              if uptime_s > 0 { stats.adc_samples as u64 * 1000 / stats.uptime_ms } else { 0 },
+              stats.button_presses,
+              stats.led1_blinks,
+              stats.led2_blinks,
+              stats.posicao);
+        info!("Pose: x {} mm, y {} mm, theta {} rad, distancia {} mm",
+              stats.pose.x, stats.pose.y, stats.pose.theta,
+              stats.distancia_mm);
     }
 }
 
-#[embassy_executor::main]
-async fn main(spawner: Spawner) {
+#[entry]
+fn main() -> ! {
     let p = embassy_stm32::init(Default::default());
-    let led1 = Output::new(p.PC13, Level::Low, Speed::Low);
-    let led2 = Output::new(p.PA11, Level::Low, Speed::Low);
+
+    // Contador de ciclos usado para medir o tempo de CPU de cada tarefa
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.DCB.enable_trace();
+    core.DWT.enable_cycle_counter();
+    #[cfg(feature = "heap")]
+    inicia_heap();
+    pinta_pilha();
+    let led_estado = SaidaLed::digital(Output::new(p.PC13, Level::Low, Speed::Low));
+    // TIM1 na taxa de amostragem: o CC1 dispara o ADC e o CH4 (PA11) é o PWM do LED de calibração
+    let tim1 = SimplePwm::new(
+        p.TIM1, None, None, None, Some(PwmPin::new_ch4(p.PA11, OutputType::PushPull)),
+        hz(TAXA_AMOSTRAGEM_HZ), CountingMode::EdgeAlignedUp,
+    ).split();
+    let led_calibracao = SaidaLed::pwm(tim1.ch4);
     let button = ExtiInput::new(p.PB12, p.EXTI12, Pull::Down);
     let adc = Adc::new(p.ADC1);
+    let flash = Flash::new_blocking(p.FLASH);
+    let marcador_esquerdo = Input::new(p.PB9, Pull::Up);
+    let marcador_direito = Input::new(p.PB8, Pull::Up);
+    let motores = Motores::new(
+        p.TIM3, p.PB0, p.PB1,
+        p.PB13, p.PB14, p.PB15, p.PB10,
+        hz(FREQUENCIA_PADRAO_HZ),
+    );
+    // TIM4 ficou livre para o encoder direito ao mover o time driver para o TIM5
+    let encoder_esquerdo = Qei::new(p.TIM2, QeiPin::new_ch1(p.PA15), QeiPin::new_ch2(p.PB3));
+    let encoder_direito = Qei::new(p.TIM4, QeiPin::new_ch1(p.PB6), QeiPin::new_ch2(p.PB7));
 
     let mut config = Config::default();
     config.baudrate = 9600; 
@@ -268,17 +1250,30 @@ async fn main(spawner: Spawner) {
         config,
     ).unwrap();
 
-    spawner.spawn(console_shell(uart)).unwrap();
-    spawner.spawn(blink_fast(led1)).unwrap();
-    spawner.spawn(blink_slow(led2)).unwrap();
-    spawner.spawn(button_handler(button)).unwrap();
-    spawner.spawn(adc_task(
-        adc, p.PA0, p.PA1, p.PA2, p.PA3, 
+    // Aquisição e controle no executor de alta prioridade: uma escrita lenta
+    // no console não atrasa mais a amostragem
+    interrupt::USART2.set_priority(Priority::P6);
+    let alta = EXECUTOR_ALTA.start(interrupt::USART2);
+    inicia_alta(&alta, "adc_task", adc_task(
+        adc, p.DMA2_CH0, tim1.ch1, marcador_esquerdo, marcador_direito, p.PA0, p.PA1, p.PA2, p.PA3, 
         p.PA4, p.PA5, p.PA6, p.PA7
-    )).unwrap();
-    spawner.spawn(system_monitor()).unwrap();
+    ), __adc_task_task);
+    inicia_alta(&alta, "motores_task", motores_task(motores), __motores_task_task);
+    inicia_alta(&alta, "encoder_task", encoder_task(encoder_esquerdo, encoder_direito), __encoder_task_task);
+    inicia_alta(&alta, "controle_task", controle_task(), __controle_task_task);
 
-    loop {
-        Timer::after_secs(10).await;
-    }
-}
\ No newline at end of file
+    // O resto fica no thread mode, que dorme com WFE quando não há trabalho
+    let executor = EXECUTOR_BAIXA.init(Executor::new());
+    executor.run(|spawner| {
+        inicia(&spawner, "console_shell", console_shell(uart), __console_shell_task);
+        inicia(&spawner, "led_estado", led_task(led_estado, Led::Estado, padrao_modo(Modo::Ocioso)), __led_task_task);
+        inicia(&spawner, "led_calibracao", led_task(led_calibracao, Led::Calibracao, padrao_calibracao(&Calibracao::new())), __led_task_task);
+        inicia(&spawner, "button_handler", button_handler(button), __button_handler_task);
+        inicia(&spawner, "modo_botao", modo_botao(), __modo_botao_task);
+        inicia(&spawner, "mapa_task", mapa_task(flash), __mapa_task_task);
+        inicia(&spawner, "voltas_task", voltas_task(), __voltas_task_task);
+        inicia(&spawner, "calibracao_task", calibracao_task(), __calibracao_task_task);
+        inicia(&spawner, "falha_task", falha_task(), __falha_task_task);
+        inicia(&spawner, "system_monitor", system_monitor(), __system_monitor_task);
+    });
+}
@@ -0,0 +1,54 @@
+// Comandos do console: PID de linha e início/fim do seguidor
+
+use super::*;
+
+pub struct ComandoPid;
+
+impl Command for ComandoPid {
+    const NOME: &'static str = "pid";
+    const AJUDA: &'static str = "ganhos e correção do PID";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let ganhos = ganhos_pid();
+        let stats = SYSTEM_STATS.le();
+        escreve(saida, format_args!(
+            "\n=== Controle PID ===\r\n\
+             Kp: {}  Ki: {}  Kd: {}\r\n\
+             Período: {} us\r\n\
+             Posição: {}  Correção: {}\r\n",
+            ganhos.kp, ganhos.ki, ganhos.kd, CONTROLE_PERIODO_US,
+            stats.posicao, stats.correcao
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct Seguir;
+
+impl Command for Seguir {
+    const NOME: &'static str = "seguir";
+    const AJUDA: &'static str = "liga o seguidor de linha";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        match muda_modo(Modo::Seguindo).await {
+            Ok(()) => escreve(saida, format_args!("\nSeguidor ligado\r\n")).await,
+            Err(e) => escreve(saida, format_args!("\nNão é possível seguir no modo {}\r\n", e.de.nome())).await,
+        }
+        Ok(())
+    }
+}
+
+pub struct Parar;
+
+impl Command for Parar {
+    const NOME: &'static str = "parar";
+    const AJUDA: &'static str = "desliga o seguidor e freia os motores";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        match muda_modo(Modo::Parado).await {
+            Ok(()) => escreve(saida, format_args!("\nSeguidor desligado\r\n")).await,
+            Err(e) => escreve(saida, format_args!("\nNão é possível parar no modo {}\r\n", e.de.nome())).await,
+        }
+        Ok(())
+    }
+}
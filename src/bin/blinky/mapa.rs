@@ -0,0 +1,127 @@
+// Comandos do console: mapa da pista e contagem de voltas
+
+use super::*;
+
+pub struct Mapa;
+
+impl Command for Mapa {
+    const NOME: &'static str = "mapa";
+    const ARGUMENTOS: &'static str = "[aprender|fim|correr|salva|carrega]";
+    const AJUDA: &'static str = "lista os trechos, aprende a pista, segue o mapa ou usa a flash";
+    const OPCOES: &'static [&'static str] = &["aprender", "fim", "correr", "salva", "carrega"];
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        match args {
+            "" => {
+                // O mapa inteiro não cabe em uma resposta, então cada trecho é enviado separado
+                let (quantidade, comprimento) = MAPA.lock(|m| {
+                    let m = m.borrow();
+                    (m.trechos().len(), m.comprimento_mm())
+                });
+                escreve(saida, format_args!(
+                    "\n=== Mapa da Pista ===\r\n{} trechos, {} mm\r\n", quantidade, comprimento as i32
+                )).await;
+                for i in 0..quantidade {
+                    let Some(t) = MAPA.lock(|m| m.borrow().trechos().get(i).copied()) else {
+                        break;
+                    };
+                    escreve(saida, format_args!(
+                        "{}: {:?} {}..{} mm, raio min {} mm\r\n",
+                        i, t.tipo, t.inicio_mm as i32, t.fim_mm as i32,
+                        if t.curvatura_max > 0.0 { (1.0 / t.curvatura_max) as i32 } else { 0 }
+                    )).await;
+                }
+            }
+            "aprender" => {
+                if !pode_seguir() {
+                    escreve(saida, format_args!("\nNão é possível seguir no modo {}\r\n", modo_atual().nome())).await;
+                    return Ok(());
+                }
+                APRENDIZADO.lock(|a| a.borrow_mut().reinicia());
+                let pista = MODO_PISTA.swap(PISTA_APRENDENDO, Ordering::Relaxed);
+                if let Err(e) = muda_modo(Modo::Seguindo).await {
+                    MODO_PISTA.store(pista, Ordering::Relaxed);
+                    escreve(saida, format_args!("\nNão é possível seguir no modo {}\r\n", e.de.nome())).await;
+                    return Ok(());
+                }
+                escreve(saida, format_args!(
+                    "\nAprendendo a pista a {} mm/s até a volta completa ou 'mapa fim'\r\n",
+                    parametro(Param::VelocidadeAprendizado)
+                )).await;
+            }
+            "fim" => {
+                if MODO_PISTA.load(Ordering::Relaxed) != PISTA_APRENDENDO {
+                    escreve(saida, format_args!("\nNenhuma volta de aprendizado em andamento\r\n")).await;
+                    return Ok(());
+                }
+                let (trechos, comprimento) = finaliza_aprendizado();
+                escreve(saida, format_args!("\nMapa com {} trechos, {} mm\r\n", trechos, comprimento as i32)).await;
+            }
+            "correr" => {
+                if !MAPA.lock(|m| m.borrow().valido()) {
+                    escreve(saida, format_args!("\nSem mapa: use 'mapa aprender' ou 'mapa carrega'\r\n")).await;
+                    return Ok(());
+                }
+                if !pode_seguir() {
+                    escreve(saida, format_args!("\nNão é possível seguir no modo {}\r\n", modo_atual().nome())).await;
+                    return Ok(());
+                }
+                // A volta começa onde o robô está agora, que deve ser o ponto de partida do aprendizado
+                let distancia = SYSTEM_STATS.com(|s| s.distancia_mm);
+                INICIO_VOLTA_MM.lock(|i| i.set(distancia));
+                let pista = MODO_PISTA.swap(PISTA_MAPA, Ordering::Relaxed);
+                if let Err(e) = muda_modo(Modo::Seguindo).await {
+                    MODO_PISTA.store(pista, Ordering::Relaxed);
+                    escreve(saida, format_args!("\nNão é possível seguir no modo {}\r\n", e.de.nome())).await;
+                    return Ok(());
+                }
+                escreve(saida, format_args!("\nSeguindo com o mapa da pista\r\n")).await;
+            }
+            "salva" | "carrega" => {
+                let operacao = if args == "salva" { OperacaoFlash::Salva } else { OperacaoFlash::Carrega };
+                // A F411 tem um banco só: apagando, nenhuma instrução é buscada, nem a da malha de controle
+                if args == "salva" && !matches!(modo_atual(), Modo::Ocioso | Modo::Parado) {
+                    escreve(saida, format_args!("\nSalvar só com o robô ocioso ou parado (agora {})\r\n", modo_atual().nome())).await;
+                    return Ok(());
+                }
+                RESULTADO_FLASH.reset();
+                OPERACAO_FLASH.send(operacao).await;
+                match RESULTADO_FLASH.wait().await {
+                    Ok(trechos) => escreve(saida, format_args!("\n{:?}: {} trechos\r\n", operacao, trechos)).await,
+                    Err(e) => escreve(saida, format_args!("\n{:?} falhou: {:?}\r\n", operacao, e)).await,
+                }
+            }
+            _ => return Err(ErroComando::ArgumentoInvalido),
+        }
+        Ok(())
+    }
+}
+
+pub struct Voltas;
+
+impl Command for Voltas {
+    const NOME: &'static str = "voltas";
+    const ARGUMENTOS: &'static str = "[n]";
+    const AJUDA: &'static str = "voltas completas; com n, para após n voltas (0 sem limite)";
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        if !args.is_empty() {
+            let valor = args.parse::<u32>().map_err(|_| ErroComando::ArgumentoInvalido)?;
+            VOLTAS.lock(|v| v.borrow_mut().voltas_alvo = valor);
+            escreve(saida, format_args!("Parar após {} voltas (0 = sem limite)\r\n", valor)).await;
+            return Ok(());
+        }
+
+        let (voltas, alvo, largou) = VOLTAS.lock(|v| {
+            let v = v.borrow();
+            (v.voltas(), v.voltas_alvo, v.largou())
+        });
+        escreve(saida, format_args!(
+            "\n=== Voltas ===\r\n\
+             Largou: {}  Voltas: {} de {}\r\n\
+             Marcadores de curva: {}\r\n",
+            largou, voltas, alvo, SYSTEM_STATS.com(|s| s.marcadores_curva)
+        )).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+// Comandos do console: teste de bancada dos motores, em duty ou em velocidade
+
+use super::*;
+
+pub struct Motor;
+
+impl Command for Motor {
+    const NOME: &'static str = "motor";
+    const ARGUMENTOS: &'static str = "e|d|ambos <duty> | freio | livre | freq <hz>";
+    const AJUDA: &'static str = "teste de bancada dos motores (duty -1000..1000, freq 100..100000)";
+    const OPCOES: &'static [&'static str] = &["e", "d", "ambos", "freio", "livre", "freq"];
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let mut args = args.split_whitespace();
+        let valida = |duty: i32| (-(DUTY_MAX as i32)..=DUTY_MAX as i32).contains(&duty);
+        let frequencia_valida = |f: i32| (FREQUENCIA_MIN_HZ as i32..=FREQUENCIA_MAX_HZ as i32).contains(&f);
+        let comando = match (args.next(), args.next().map(|v| v.parse::<i32>())) {
+            (Some("e"), Some(Ok(duty))) if valida(duty) => ComandoMotores::Motor(Lado::Esquerdo, duty as i16),
+            (Some("d"), Some(Ok(duty))) if valida(duty) => ComandoMotores::Motor(Lado::Direito, duty as i16),
+            (Some("ambos"), Some(Ok(duty))) if valida(duty) => ComandoMotores::Duty { esquerdo: duty as i16, direito: duty as i16 },
+            (Some("freio"), None) => ComandoMotores::Para(Parada::Freio),
+            (Some("livre"), None) => ComandoMotores::Para(Parada::Livre),
+            (Some("freq"), Some(Ok(f))) if frequencia_valida(f) => ComandoMotores::Frequencia(f as u32),
+            _ => return Err(ErroComando::ArgumentoInvalido),
+        };
+
+        if !bancada_liberada() {
+            escreve(saida, format_args!("Motores só em teste de bancada, no modo bancada (agora {})\r\n", modo_atual().nome())).await;
+            return Ok(());
+        }
+
+        // Comando manual desliga o seguidor e a malha de velocidade para não sobrescreverem o duty
+        SEGUINDO.store(false, Ordering::Relaxed);
+        MALHA_VELOCIDADE.store(false, Ordering::Relaxed);
+        ponte::comanda(comando).await;
+        escreve(saida, format_args!("Motores: {:?}\r\n", comando)).await;
+        Ok(())
+    }
+}
+
+pub struct Vel;
+
+impl Command for Vel {
+    const NOME: &'static str = "vel";
+    const ARGUMENTOS: &'static str = "<e> <d>";
+    const AJUDA: &'static str = "velocidade das rodas em mm/s, malha fechada (até +-2000)";
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let mut args = args.split_whitespace().map(|v| v.parse::<f32>().ok().and_then(limita_alvo));
+        let (Some(Some(esquerdo)), Some(Some(direito)), None) = (args.next(), args.next(), args.next()) else {
+            return Err(ErroComando::ArgumentoInvalido);
+        };
+        if !bancada_liberada() {
+            escreve(saida, format_args!("Motores só em teste de bancada, no modo bancada (agora {})\r\n", modo_atual().nome())).await;
+            return Ok(());
+        }
+        SEGUINDO.store(false, Ordering::Relaxed);
+        ALVO_VELOCIDADE.lock(|a| a.set((esquerdo, direito)));
+        MALHA_VELOCIDADE.store(true, Ordering::Relaxed);
+        escreve(saida, format_args!("Velocidade alvo: {} / {} mm/s\r\n", esquerdo, direito)).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+// Comandos do console: encoders e pose estimada
+
+use super::*;
+
+pub struct Encoder;
+
+impl Command for Encoder {
+    const NOME: &'static str = "encoder";
+    const AJUDA: &'static str = "contagem e velocidade das rodas";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let stats = SYSTEM_STATS.le();
+        let alvo = ALVO_VELOCIDADE.lock(|a| a.get());
+        escreve(saida, format_args!(
+            "\n=== Encoders ===\r\n\
+             Esquerdo: {} ticks, {} ticks/s, {} mm/s\r\n\
+             Direito: {} ticks, {} ticks/s, {} mm/s\r\n\
+             Alvo: {} / {} mm/s (malha {})\r\n",
+            stats.ticks_esquerdo, stats.velocidade_esquerdo.ticks_s, stats.velocidade_esquerdo.mm_s as i32,
+            stats.ticks_direito, stats.velocidade_direito.ticks_s, stats.velocidade_direito.mm_s as i32,
+            alvo.0 as i32, alvo.1 as i32,
+            if MALHA_VELOCIDADE.load(Ordering::Relaxed) { "ligada" } else { "desligada" }
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct ComandoPose;
+
+impl Command for ComandoPose {
+    const NOME: &'static str = "pose";
+    const AJUDA: &'static str = "posição estimada pela odometria";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let stats = SYSTEM_STATS.le();
+        escreve(saida, format_args!(
+            "\n=== Odometria ===\r\n\
+             x: {} mm  y: {} mm  theta: {} graus\r\n\
+             Distância percorrida: {} mm\r\n",
+            stats.pose.x as i32, stats.pose.y as i32, stats.pose.theta.to_degrees() as i32,
+            stats.distancia_mm as i32
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct PoseZera;
+
+impl Command for PoseZera {
+    const NOME: &'static str = "pose_zera";
+    const AJUDA: &'static str = "volta a pose para a origem";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        ZERA_POSE.store(true, Ordering::Relaxed);
+        escreve(saida, format_args!("\nPose zerada\r\n")).await;
+        Ok(())
+    }
+}
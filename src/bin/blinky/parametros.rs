@@ -0,0 +1,97 @@
+// Comandos do console: parâmetros ajustáveis: params, get e set
+
+use super::*;
+
+async fn mostra_parametro<W: Write>(saida: &mut W, definicao: &Parametro, valor: Valor) {
+    if let Valor::Bool(_) = valor {
+        escreve(saida, format_args!("{} = {} - {}\r\n", definicao.nome, valor, definicao.descricao)).await;
+    } else {
+        escreve(saida, format_args!(
+            "{} = {} {} [{}..{}] - {}\r\n",
+            definicao.nome, valor, definicao.unidade, definicao.min, definicao.max, definicao.descricao
+        )).await;
+    }
+}
+
+pub struct Params;
+
+impl Command for Params {
+    const NOME: &'static str = "params";
+    const AJUDA: &'static str = "lista os parâmetros ajustáveis";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        escreve(saida, format_args!("\n=== Parâmetros ===\r\n")).await;
+        let definicoes = PARAMETROS.lock(|t| t.borrow().definicoes());
+        for (i, definicao) in definicoes.iter().enumerate() {
+            let valor = PARAMETROS.lock(|t| t.borrow().valor(i));
+            mostra_parametro(saida, definicao, valor).await;
+        }
+        Ok(())
+    }
+}
+
+pub struct Get;
+
+impl Command for Get {
+    const NOME: &'static str = "get";
+    const ARGUMENTOS: &'static str = "<nome>";
+    const AJUDA: &'static str = "mostra um parâmetro";
+    const OPCOES: &'static [&'static str] = &NOMES_PARAMETROS;
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let parametro = PARAMETROS.lock(|t| {
+            let t = t.borrow();
+            t.indice(args).map(|i| (&t.definicoes()[i], t.valor(i)))
+        });
+        let Some((definicao, valor)) = parametro else {
+            escreve(saida, format_args!("Parâmetro desconhecido: {}. Veja 'params'\r\n", args)).await;
+            return Ok(());
+        };
+        mostra_parametro(saida, definicao, valor).await;
+        Ok(())
+    }
+}
+
+pub struct Set;
+
+impl Command for Set {
+    const NOME: &'static str = "set";
+    const ARGUMENTOS: &'static str = "<nome> <valor>";
+    const AJUDA: &'static str = "altera um parâmetro";
+    const OPCOES: &'static [&'static str] = &NOMES_PARAMETROS;
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let (nome, texto) = divide_comando(args);
+        if texto.is_empty() {
+            return Err(ErroComando::ArgumentoInvalido);
+        }
+        let alteracao = PARAMETROS.lock(|t| {
+            let mut t = t.borrow_mut();
+            let i = t.indice(nome)?;
+            let resultado = t.define_texto(nome, texto).map(|_| t.valor(i));
+            Some((i, &t.definicoes()[i], resultado))
+        });
+        let Some((i, definicao, resultado)) = alteracao else {
+            escreve(saida, format_args!("Parâmetro desconhecido: {}. Veja 'params'\r\n", nome)).await;
+            return Ok(());
+        };
+
+        match resultado {
+            Ok(valor) => {
+                ALTERACOES.immediate_publisher().publish_immediate(i);
+                escreve(saida, format_args!("{} = {}\r\n", nome, valor)).await;
+            }
+            Err(_) if matches!(definicao.padrao, Valor::Bool(_)) => {
+                escreve(saida, format_args!(
+                    "Valor inválido para {}: esperado true ou false (também 1/0, on/off)\r\n", nome
+                )).await;
+            }
+            Err(_) => {
+                escreve(saida, format_args!(
+                    "Valor inválido para {}: esperado {} a {}\r\n", nome, definicao.min, definicao.max
+                )).await;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+// Comandos do console: calibração do array de sensores
+
+use super::*;
+
+pub struct Calibrar;
+
+impl Command for Calibrar {
+    const NOME: &'static str = "calibrar";
+    const AJUDA: &'static str = "inicia a calibração dos sensores";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        if let Err(e) = muda_modo(Modo::Calibrando).await {
+            escreve(saida, format_args!("Não é possível calibrar no modo {}\r\n", e.de.nome())).await;
+            return Ok(());
+        }
+        escreve(saida, format_args!(
+            "Calibração iniciada: passe o robô sobre a linha por {} ms\r\n", parametro(Param::CalibracaoMs)
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct ComandoCalibracao;
+
+impl Command for ComandoCalibracao {
+    const NOME: &'static str = "calibracao";
+    const AJUDA: &'static str = "min/max de cada sensor";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let cal = CALIBRACAO.lock(|c| *c.borrow());
+        escreve(saida, format_args!(
+            "\n=== Calibração dos Sensores ===\r\n\
+             Amostras: {}  Em andamento: {}  Válida: {}\r\n",
+            cal.amostras, CALIBRANDO.load(Ordering::Relaxed), cal.valida()
+        )).await;
+        if cal.amostras > 0 {
+            for i in 0..NUM_SENSORES {
+                escreve(saida, format_args!(
+                    "S{}: min {} max {}{}\r\n",
+                    i, cal.min[i], cal.max[i],
+                    if cal.canal_valido(i) { "" } else { " (sem faixa)" }
+                )).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct CalibracaoLimpa;
+
+impl Command for CalibracaoLimpa {
+    const NOME: &'static str = "calibracao_limpa";
+    const AJUDA: &'static str = "descarta a calibração";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        CALIBRACAO.lock(|c| c.borrow_mut().limpa());
+        mostra_padrao(Led::Calibracao, padrao_calibracao(&Calibracao::new()));
+        escreve(saida, format_args!("\nCalibração descartada, usando leituras brutas\r\n")).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+// Comandos do console: status, modo, tarefas, tempo real e memória
+
+use super::*;
+use heapless::Vec;
+use seguidor::tempo_real::EventoRt;
+
+pub struct Status;
+
+impl Command for Status {
+    const NOME: &'static str = "status";
+    const AJUDA: &'static str = "estatísticas do sistema";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let stats = SYSTEM_STATS.le();
+        escreve(saida, format_args!(
+            "\n=== Status do Sistema ===\r\n\
+             Uptime: {} ms\r\n\
+             Modo: {}\r\n\
+             Tarefas ativas: {}\r\n\
+             Botão pressionado: {} vezes\r\n\
+             LED1 piscou: {} vezes\r\n\
+             LED2 piscou: {} vezes\r\n\
+             ADC Samples: {} vezes ({} overruns)\r\n\
+             Posição do peso: {}\r\n\
+             Linha: {:?} (perdida {} vezes)\r\n\
+             Correção PID: {}\r\n",
+            stats.uptime_ms, modo_atual().nome(), TAREFAS.com(|t| t.tarefas().len()),
+            stats.button_presses, stats.led1_blinks, stats.led2_blinks,
+            stats.adc_samples, stats.adc_overruns, stats.posicao, stats.linha, stats.linha_perdida,
+            stats.correcao
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct Reset;
+
+impl Command for Reset {
+    const NOME: &'static str = "reset";
+    const AJUDA: &'static str = "zera os contadores de botão e LEDs";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        SYSTEM_STATS.atualiza(|s| {
+            s.button_presses = 0;
+            s.led1_blinks = 0;
+            s.led2_blinks = 0;
+        });
+        escreve(saida, format_args!("\nEstatísticas resetadas!\r\n")).await;
+        Ok(())
+    }
+}
+
+pub struct ComandoModo;
+
+// Modos que podem ser pedidos: falha só entra por falha(), nunca a pedido
+const MODOS_PEDIDOS: [&str; 6] = ["ocioso", "calibrando", "armado", "seguindo", "parado", "bancada"];
+
+impl Command for ComandoModo {
+    const NOME: &'static str = "mode";
+    const ARGUMENTOS: &'static str = "[ocioso|calibrando|armado|seguindo|parado|bancada]";
+    const AJUDA: &'static str = "mostra ou muda o modo de operação";
+    const OPCOES: &'static [&'static str] = &MODOS_PEDIDOS;
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        if args.is_empty() {
+            let (modo, desde, falha, transicoes) = MODO.com(|m| (m.modo(), m.desde_ms(), m.falha(), m.transicoes()));
+            escreve(saida, format_args!(
+                "\nModo: {} há {} ms ({} transições)\r\nÚltima falha: {:?}\r\n",
+                modo.nome(), Instant::now().as_millis() - desde, transicoes, falha
+            )).await;
+            return Ok(());
+        }
+        let destino = Modo::de_nome(args).ok_or(ErroComando::ArgumentoInvalido)?;
+        match muda_modo(destino).await {
+            Ok(()) => escreve(saida, format_args!("\nModo: {}\r\n", destino.nome())).await,
+            Err(e) => escreve(saida, format_args!(
+                "\nNão é possível ir de {} para {}\r\n", e.de.nome(), e.para.nome()
+            )).await,
+        }
+        Ok(())
+    }
+}
+
+pub struct Tasks;
+
+impl Command for Tasks {
+    const NOME: &'static str = "tasks";
+    const AJUDA: &'static str = "tarefas instaladas e tempo de CPU de cada uma";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let agora = DWT::cycle_count();
+        let total = TAREFAS.atualiza(|t| t.avanca(agora));
+        escreve(saida, format_args!(
+            "\n=== Tarefas ===\r\n{:<16} {:<8} {:<10} {:>8} {:>12} {:>8} {:>6}\r\n",
+            "Nome", "Prio", "Estado", "Polls", "Ciclos", "Max", "CPU"
+        )).await;
+        let mut usado = 0;
+        for i in 0..MAX_TAREFAS {
+            // Uma tarefa por vez para não segurar a seção crítica enquanto escreve
+            let linha = TAREFAS.com(|t| t.tarefas().get(i).map(|tarefa| (*tarefa, t.permil(tarefa))));
+            let Some((tarefa, permil)) = linha else {
+                break;
+            };
+            usado += permil;
+            escreve(saida, format_args!(
+                "{:<16} {:<8} {:<10} {:>8} {:>12} {:>8} {:>3}.{}%\r\n",
+                tarefa.nome, tarefa.prioridade, tarefa.estado.nome(), tarefa.polls,
+                tarefa.ciclos, tarefa.ciclos_max, permil / 10, permil % 10
+            )).await;
+        }
+        let ocioso = 1000u32.saturating_sub(usado);
+        escreve(saida, format_args!(
+            "Ocioso: {}.{}% de {} ciclos\r\n", ocioso / 10, ocioso % 10, total
+        )).await;
+        Ok(())
+    }
+}
+
+pub struct Rt;
+
+async fn mostra_faixa<W: Write>(saida: &mut W, nome: &str, faixa: &Faixa) {
+    escreve(saida, format_args!(
+        "  {:<10} min {:>6}  med {:>6}  max {:>6} us\r\n",
+        nome, faixa.minimo() / CICLOS_POR_US, faixa.media() / CICLOS_POR_US, faixa.max / CICLOS_POR_US
+    )).await;
+}
+
+// Comprimento da barra da faixa mais cheia
+const BARRA_HISTOGRAMA: &str = "########################################";
+
+async fn mostra_histograma<W: Write>(saida: &mut W, titulo: &str, histograma: &Histograma) {
+    escreve(saida, format_args!("\n{} (faixas de {} us)\r\n", titulo, histograma.largura / CICLOS_POR_US)).await;
+    let maior = histograma.maior().max(1) as u64;
+    for (i, &contagem) in histograma.contagens.iter().enumerate() {
+        let inicio = i as u32 * histograma.largura / CICLOS_POR_US;
+        let largura = (contagem as u64 * BARRA_HISTOGRAMA.len() as u64).div_ceil(maior) as usize;
+        let barra = &BARRA_HISTOGRAMA[..largura];
+        if i == NUM_FAIXAS - 1 {
+            escreve(saida, format_args!("  >= {:>6} us |{} {}\r\n", inicio, barra, contagem)).await;
+        } else {
+            let fim = (i as u32 + 1) * histograma.largura / CICLOS_POR_US;
+            escreve(saida, format_args!("{:>6}-{:<6} us |{} {}\r\n", inicio, fim, barra, contagem)).await;
+        }
+    }
+}
+
+// Tempos relativos à primeira liberação do rastro. Copia o rastro de uma vez
+// para não misturar eventos que entram enquanto a saída está sendo escrita
+async fn mostra_rastro<W: Write>(saida: &mut W) {
+    let (congelado, rastro) = RT.com(|r| {
+        let rastro: Vec<(EventoRt, &'static str), TAMANHO_RASTRO> = r
+            .rastro()
+            .map(|e| (*e, r.tarefas().get(e.tarefa).map_or("?", |t| t.nome)))
+            .collect();
+        (r.congelado(), rastro)
+    });
+    escreve(saida, format_args!(
+        "\n=== Rastro de tempo real ({}) ===\r\n{:<16} {:>10} {:>9} {:>9}\r\n",
+        if congelado { "congelado na perda de prazo" } else { "gravando" },
+        "Tarefa", "Liberação", "Latência", "Execução"
+    )).await;
+    let mut origem = None;
+    for (evento, nome) in rastro {
+        let origem = *origem.get_or_insert(evento.liberacao);
+        escreve(saida, format_args!(
+            "{:<16} {:>7} us {:>6} us {:>6} us{}\r\n",
+            nome,
+            evento.liberacao.wrapping_sub(origem) / CICLOS_POR_US,
+            evento.inicio.wrapping_sub(evento.liberacao) / CICLOS_POR_US,
+            evento.fim.wrapping_sub(evento.inicio) / CICLOS_POR_US,
+            if evento.perdeu { "  PRAZO PERDIDO" } else { "" }
+        )).await;
+    }
+}
+
+impl Command for Rt {
+    const NOME: &'static str = "rt";
+    const ARGUMENTOS: &'static str = "[reset | hist <tarefa> | trace]";
+    const AJUDA: &'static str = "prazos das tarefas de tempo real: latência, execução e jitter";
+    const OPCOES: &'static [&'static str] = &["reset", "hist", "trace"];
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        match divide_comando(args) {
+            ("", _) => {}
+            ("reset", _) => {
+                RT.atualiza(|r| r.zera());
+                escreve(saida, format_args!("\nEstatísticas de tempo real zeradas, rastro gravando\r\n")).await;
+                return Ok(());
+            }
+            ("hist", nome) => {
+                let tarefa = RT.com(|r| r.indice(nome).map(|i| r.tarefas()[i]));
+                let Some(tarefa) = tarefa else {
+                    return Err(ErroComando::ArgumentoInvalido);
+                };
+                escreve(saida, format_args!("\n=== {} ===\r\n", tarefa.nome)).await;
+                mostra_histograma(saida, "Execução", &tarefa.hist_execucao).await;
+                mostra_histograma(saida, "Jitter", &tarefa.hist_jitter).await;
+                return Ok(());
+            }
+            ("trace", _) => {
+                mostra_rastro(saida).await;
+                return Ok(());
+            }
+            _ => return Err(ErroComando::ArgumentoInvalido),
+        }
+        escreve(saida, format_args!("\n=== Tempo real ===\r\n")).await;
+        for i in 0..MAX_TAREFAS_RT {
+            let Some(tarefa) = RT.com(|r| r.tarefas().get(i).copied()) else {
+                break;
+            };
+            escreve(saida, format_args!(
+                "{}: período {} us, prazo {} us, {} ativações, {} prazos perdidos\r\n",
+                tarefa.nome, tarefa.periodo / CICLOS_POR_US, tarefa.prazo / CICLOS_POR_US,
+                tarefa.ativacoes, tarefa.perdas
+            )).await;
+            mostra_faixa(saida, "latência", &tarefa.latencia).await;
+            mostra_faixa(saida, "execução", &tarefa.execucao).await;
+            mostra_faixa(saida, "resposta", &tarefa.resposta).await;
+            mostra_faixa(saida, "jitter", &tarefa.jitter).await;
+        }
+        Ok(())
+    }
+}
+
+pub struct Mem;
+
+impl Command for Mem {
+    const NOME: &'static str = "mem";
+    const AJUDA: &'static str = "uso de RAM: .data/.bss, pico da pilha e uso da arena de tarefas";
+
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let (inicio, topo) = regiao_pilha();
+        let intocado = unsafe { memoria::intocado(inicio, topo) };
+        let sp = cortex_m::register::msp::read() as usize;
+        let pilha = UsoPilha::calcula(inicio as usize, topo as usize, sp, intocado);
+        let (data, bss) = unsafe {
+            (
+                addr_of!(__edata) as usize - addr_of!(__sdata) as usize,
+                addr_of!(__ebss) as usize - addr_of!(__sbss) as usize,
+            )
+        };
+        let arena = TAREFAS.com(|t| t.tamanho_total());
+        escreve(saida, format_args!(
+            "\n=== Memória ===\r\n\
+             .data: {} bytes  .bss: {} bytes\r\n\
+             Pilha: pico {} de {} bytes, {} nunca usados, {} em uso agora\r\n\
+             Arena de tarefas: {} de {} bytes, {} livres (sem o alinhamento)\r\n",
+            data, bss, pilha.pico, pilha.total, pilha.livre(), pilha.atual,
+            arena, ARENA_TAREFAS, ARENA_TAREFAS.saturating_sub(arena)
+        )).await;
+        for i in 0..MAX_TAREFAS {
+            let tarefa = TAREFAS.com(|t| t.tarefas().get(i).map(|tarefa| (tarefa.nome, tarefa.tamanho)));
+            let Some((nome, tamanho)) = tarefa else {
+                break;
+            };
+            if tamanho > 0 {
+                escreve(saida, format_args!("  {}: {} bytes\r\n", nome, tamanho)).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Heap;
+
+impl Command for Heap {
+    const NOME: &'static str = "heap";
+    const AJUDA: &'static str = "uso do alocador global (feature heap)";
+
+    #[cfg(feature = "heap")]
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        let uso = HEAP.uso();
+        escreve(saida, format_args!(
+            "\n=== Heap ===\r\n\
+             Total: {} bytes\r\n\
+             Usado: {} bytes (pico {})\r\n\
+             Livre: {} bytes\r\n\
+             Alocações: {} ({} falhas)\r\n",
+            uso.total, uso.usado, uso.pico, uso.livre, uso.alocacoes, uso.falhas
+        )).await;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "heap"))]
+    async fn executa<W: Write>(&self, _args: &str, saida: &mut W) -> Result<(), ErroComando> {
+        escreve(saida, format_args!("\nSem alocador: compile com --features heap\r\n")).await;
+        Ok(())
+    }
+}
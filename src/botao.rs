@@ -0,0 +1,173 @@
+// Reconhecimento de gestos do botão da placa
+//
+// Recebe o nível do botão (true = apertado) com o instante em ms e devolve no
+// máximo um evento por chamada. A mudança de nível só vale depois de ficar
+// estável por DEBOUNCE_MS; o segundo aperto logo depois de um aperto curto
+// vira CliqueDuplo no lugar de Aperto. Um aperto curto sem segundo aperto em
+// DUPLO_MS vira Clique, para quem não pode agir num gesto que ainda vai mudar.
+
+// Tempo que o nível precisa ficar parado para contar como mudança
+pub const DEBOUNCE_MS: u32 = 20;
+// Segurando por mais que isso gera ApertoLongo, ainda com o botão apertado
+pub const LONGO_MS: u32 = 800;
+// Intervalo máximo entre soltar e apertar de novo no clique duplo
+pub const DUPLO_MS: u32 = 300;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum EventoBotao {
+    Aperto,
+    // Tempo segurado até o evento, em ms
+    ApertoLongo(u32),
+    CliqueDuplo,
+    Soltura,
+    // Aperto curto confirmado: solto e sem segundo aperto até DUPLO_MS
+    Clique,
+}
+
+pub struct Botao {
+    // Nível já filtrado
+    estavel: bool,
+    // Última leitura e quando ela mudou
+    bruto: bool,
+    desde_ms: u32,
+    apertado_ms: u32,
+    longo_emitido: bool,
+    // Soltura de um aperto curto que ainda pode virar clique duplo
+    soltura_ms: Option<u32>,
+    duplo: bool,
+}
+
+impl Botao {
+    pub const fn new() -> Self {
+        Self {
+            estavel: false,
+            bruto: false,
+            desde_ms: 0,
+            apertado_ms: 0,
+            longo_emitido: false,
+            soltura_ms: None,
+            duplo: false,
+        }
+    }
+
+    pub fn atualiza(&mut self, nivel: bool, agora_ms: u32) -> Option<EventoBotao> {
+        if nivel != self.bruto {
+            self.bruto = nivel;
+            self.desde_ms = agora_ms;
+        }
+
+        if self.bruto != self.estavel && agora_ms.wrapping_sub(self.desde_ms) >= DEBOUNCE_MS {
+            self.estavel = self.bruto;
+            // Os tempos contam da primeira borda, não do fim do debounce
+            return Some(if self.estavel { self.aperta() } else { self.solta() });
+        }
+
+        if self.estavel && !self.longo_emitido {
+            let segurado = agora_ms.wrapping_sub(self.apertado_ms);
+            if segurado >= LONGO_MS {
+                self.longo_emitido = true;
+                return Some(EventoBotao::ApertoLongo(segurado));
+            }
+        }
+
+        if !self.estavel && !self.bruto {
+            if let Some(soltura) = self.soltura_ms {
+                if agora_ms.wrapping_sub(soltura) > DUPLO_MS {
+                    self.soltura_ms = None;
+                    return Some(EventoBotao::Clique);
+                }
+            }
+        }
+        None
+    }
+
+    // Solto, sem mudança nem clique pendente: quem chama pode esperar a próxima
+    // borda em vez de amostrar
+    pub fn ocioso(&self) -> bool {
+        !self.estavel && !self.bruto && self.soltura_ms.is_none()
+    }
+
+    fn aperta(&mut self) -> EventoBotao {
+        self.apertado_ms = self.desde_ms;
+        self.longo_emitido = false;
+        let duplo = self
+            .soltura_ms
+            .take()
+            .is_some_and(|soltura| self.desde_ms.wrapping_sub(soltura) <= DUPLO_MS);
+        self.duplo = duplo;
+        if duplo {
+            EventoBotao::CliqueDuplo
+        } else {
+            EventoBotao::Aperto
+        }
+    }
+
+    fn solta(&mut self) -> EventoBotao {
+        // Nem aperto longo nem o segundo clique de um duplo podem começar outro duplo
+        self.soltura_ms = if self.longo_emitido || self.duplo {
+            None
+        } else {
+            Some(self.desde_ms)
+        };
+        EventoBotao::Soltura
+    }
+}
+
+impl Default for Botao {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    // Mantém o nível de `de` até `ate`, amostrando a cada 5 ms como o button_handler
+    fn segura(b: &mut Botao, nivel: bool, de: u32, ate: u32) -> Vec<EventoBotao> {
+        (de..ate).step_by(5).filter_map(|t| b.atualiza(nivel, t)).collect()
+    }
+
+    #[test]
+    fn clique_so_depois_da_janela_do_duplo() {
+        let mut b = Botao::new();
+        assert_eq!(segura(&mut b, true, 0, 100), [EventoBotao::Aperto]);
+        assert_eq!(segura(&mut b, false, 100, 100 + DUPLO_MS), [EventoBotao::Soltura]);
+        // Ainda pode virar duplo, então continua amostrando
+        assert!(!b.ocioso());
+        assert_eq!(segura(&mut b, false, 100 + DUPLO_MS, 500), [EventoBotao::Clique]);
+        assert!(b.ocioso());
+    }
+
+    #[test]
+    fn duplo_e_longo_nao_viram_clique() {
+        let mut b = Botao::new();
+        segura(&mut b, true, 0, 100);
+        segura(&mut b, false, 100, 200);
+        assert_eq!(segura(&mut b, true, 200, 300), [EventoBotao::CliqueDuplo]);
+        assert_eq!(segura(&mut b, false, 300, 1000), [EventoBotao::Soltura]);
+
+        assert_eq!(
+            segura(&mut b, true, 1000, 2000),
+            [EventoBotao::Aperto, EventoBotao::ApertoLongo(LONGO_MS)]
+        );
+        assert_eq!(segura(&mut b, false, 2000, 3000), [EventoBotao::Soltura]);
+        assert!(b.ocioso());
+    }
+
+    #[test]
+    fn repique_mais_curto_que_o_debounce() {
+        let mut b = Botao::new();
+        // Ruído de 15 ms com o botão solto não aperta
+        assert!(segura(&mut b, true, 0, 15).is_empty());
+        assert!(segura(&mut b, false, 15, 1000).is_empty());
+        assert!(b.ocioso());
+
+        // Nem solta com ele apertado
+        assert_eq!(segura(&mut b, true, 1000, 1100), [EventoBotao::Aperto]);
+        assert!(segura(&mut b, false, 1100, 1100 + DEBOUNCE_MS - 5).is_empty());
+        assert!(segura(&mut b, true, 1100 + DEBOUNCE_MS - 5, 1200).is_empty());
+        assert_eq!(segura(&mut b, false, 1200, 1300), [EventoBotao::Soltura]);
+    }
+}
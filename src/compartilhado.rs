@@ -0,0 +1,39 @@
+// Estado compartilhado entre tarefas de qualquer prioridade
+//
+// O valor fica protegido por seção crítica: quem lê recebe um retrato
+// consistente de todos os campos e quem escreve altera vários campos de uma
+// vez, sem que um leitor (mesmo de outro executor) veja a alteração pela metade.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+
+pub struct Compartilhado<T> {
+    valor: Mutex<CriticalSectionRawMutex, RefCell<T>>,
+}
+
+impl<T> Compartilhado<T> {
+    pub const fn new(valor: T) -> Self {
+        Self {
+            valor: Mutex::new(RefCell::new(valor)),
+        }
+    }
+
+    // Lê só o necessário sem copiar o resto
+    pub fn com<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.valor.lock(|v| f(&v.borrow()))
+    }
+
+    // As interrupções ficam desligadas durante f, que deve ser curta
+    pub fn atualiza<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.valor.lock(|v| f(&mut v.borrow_mut()))
+    }
+}
+
+impl<T: Copy> Compartilhado<T> {
+    // Cópia do valor inteiro
+    pub fn le(&self) -> T {
+        self.valor.lock(|v| *v.borrow())
+    }
+}
@@ -0,0 +1,225 @@
+// Comandos do console: cada funcionalidade implementa Command e o binário
+// junta todos em um registro estático com registro_comandos!
+
+use core::fmt;
+use heapless::{String, Vec};
+
+pub use embedded_io_async::Write;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum ErroComando {
+    Desconhecido,
+    ArgumentoInvalido,
+}
+
+// Descrição de um comando usada pelo help e pela busca por nome
+pub struct InfoComando {
+    pub nome: &'static str,
+    pub argumentos: &'static str,
+    pub ajuda: &'static str,
+    pub opcoes: &'static [&'static str],
+}
+
+// Limite de candidatos listados ao completar com TAB
+pub const MAX_CANDIDATOS: usize = 48;
+
+// Os comandos rodam todos na tarefa do console, então o futuro não precisa ser Send
+#[allow(async_fn_in_trait)]
+pub trait Command {
+    const NOME: &'static str;
+    // Argumentos aceitos no formato do help, ex. "<ms>" ou "[e|d] <duty>"; vazio se não recebe
+    const ARGUMENTOS: &'static str = "";
+    const AJUDA: &'static str;
+    // Valores do primeiro argumento oferecidos pelo TAB
+    const OPCOES: &'static [&'static str] = &[];
+
+    async fn executa<W: Write>(&self, args: &str, saida: &mut W) -> Result<(), ErroComando>;
+}
+
+// Separa nome e argumentos. Aceita "nome valor" e a forma antiga "nome=valor" (voltas=3).
+pub fn divide_comando(linha: &str) -> (&str, &str) {
+    let linha = linha.trim();
+    match linha.find([' ', '=']) {
+        Some(i) => (&linha[..i], linha[i + 1..].trim()),
+        None => (linha, ""),
+    }
+}
+
+// Formata em um buffer local e envia; a resposta de um comando cabe em 512 bytes
+pub async fn escreve<W: Write>(saida: &mut W, args: fmt::Arguments<'_>) {
+    let mut texto = String::<512>::new();
+    let _ = fmt::write(&mut texto, args);
+    let _ = saida.write_all(texto.as_bytes()).await;
+}
+
+pub async fn escreve_ajuda<W: Write>(saida: &mut W, comandos: &[InfoComando]) {
+    escreve(saida, format_args!("\r\n=== Comandos do Sistema ===\r\n")).await;
+    escreve(saida, format_args!("help - lista os comandos\r\n")).await;
+    for c in comandos {
+        if c.argumentos.is_empty() {
+            escreve(saida, format_args!("{} - {}\r\n", c.nome, c.ajuda)).await;
+        } else {
+            escreve(saida, format_args!("{} {} - {}\r\n", c.nome, c.argumentos, c.ajuda)).await;
+        }
+    }
+}
+
+// Palavra antes do cursor e os candidatos para completá-la: nomes de comando na
+// primeira palavra, opções do comando no primeiro argumento
+pub fn candidatos<'a>(
+    linha: &'a str,
+    comandos: &'static [InfoComando],
+) -> (&'a str, Vec<&'static str, MAX_CANDIDATOS>) {
+    let mut encontrados = Vec::new();
+    let linha = linha.trim_start();
+
+    let Some((nome, args)) = linha.split_once(' ') else {
+        let nomes = core::iter::once("help").chain(comandos.iter().map(|c| c.nome));
+        for nome in nomes.filter(|n| n.starts_with(linha)) {
+            let _ = encontrados.push(nome);
+        }
+        return (linha, encontrados);
+    };
+
+    let args = args.trim_start();
+    if args.contains(' ') {
+        return (args, encontrados);
+    }
+    if let Some(comando) = comandos.iter().find(|c| c.nome == nome) {
+        for opcao in comando.opcoes.iter().filter(|o| o.starts_with(args)) {
+            let _ = encontrados.push(*opcao);
+        }
+    }
+    (args, encontrados)
+}
+
+// Maior prefixo comum entre os candidatos
+pub fn prefixo_comum<'a>(candidatos: &[&'a str]) -> &'a str {
+    let Some((primeiro, resto)) = candidatos.split_first() else {
+        return "";
+    };
+    let mut tamanho = primeiro.len();
+    for c in resto {
+        tamanho = primeiro
+            .bytes()
+            .zip(c.bytes())
+            .take(tamanho)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    &primeiro[..tamanho]
+}
+
+// Declara o registro de comandos do console, cada um pelo caminho do módulo que o define:
+//
+//     registro_comandos!(Comandos { sistema::Status, sistema::Reset, sensores::Calibrar });
+//
+// gera `Comandos::COMANDOS` (usado no help e no TAB) e `Comandos::executa(linha, saida)`
+#[macro_export]
+macro_rules! registro_comandos {
+    ($registro:ident { $($cmd:path),* $(,)? }) => {
+        struct $registro;
+
+        impl $registro {
+            const COMANDOS: &'static [$crate::console::InfoComando] = &[
+                $(
+                    $crate::console::InfoComando {
+                        nome: <$cmd as $crate::console::Command>::NOME,
+                        argumentos: <$cmd as $crate::console::Command>::ARGUMENTOS,
+                        ajuda: <$cmd as $crate::console::Command>::AJUDA,
+                        opcoes: <$cmd as $crate::console::Command>::OPCOES,
+                    },
+                )*
+            ];
+
+            #[allow(dead_code)]
+            fn procura(nome: &str) -> Option<&'static $crate::console::InfoComando> {
+                Self::COMANDOS.iter().find(|c| c.nome == nome)
+            }
+
+            async fn executa<W: $crate::console::Write>(
+                linha: &str,
+                saida: &mut W,
+            ) -> Result<(), $crate::console::ErroComando> {
+                let (nome, args) = $crate::console::divide_comando(linha);
+                if nome == "help" {
+                    $crate::console::escreve_ajuda(saida, Self::COMANDOS).await;
+                    return Ok(());
+                }
+                $(
+                    if nome == <$cmd as $crate::console::Command>::NOME {
+                        return $crate::console::Command::executa(&$cmd, args, saida).await;
+                    }
+                )*
+                Err($crate::console::ErroComando::Desconhecido)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMANDOS: &[InfoComando] = &[
+        InfoComando { nome: "status", argumentos: "", ajuda: "", opcoes: &[] },
+        InfoComando { nome: "set", argumentos: "<nome> <valor>", ajuda: "", opcoes: &["kp", "ki", "kd"] },
+        InfoComando { nome: "sensores", argumentos: "", ajuda: "", opcoes: &[] },
+        InfoComando { nome: "modo", argumentos: "<modo>", ajuda: "", opcoes: &["parado", "seguindo"] },
+        InfoComando { nome: "modos", argumentos: "", ajuda: "", opcoes: &[] },
+    ];
+
+    fn nomes(linha: &str) -> (&str, std::vec::Vec<&'static str>) {
+        let (palavra, encontrados) = candidatos(linha, COMANDOS);
+        (palavra, encontrados.iter().copied().collect())
+    }
+
+    #[test]
+    fn sem_candidatos() {
+        assert_eq!(nomes("xyz"), ("xyz", vec![]));
+        assert_eq!(nomes("modo rapido"), ("rapido", vec![]));
+        // Comando sem opções ou segundo argumento não completa
+        assert_eq!(nomes("status a"), ("a", vec![]));
+        assert_eq!(nomes("set kp 1"), ("kp 1", vec![]));
+        assert_eq!(prefixo_comum(&[]), "");
+    }
+
+    #[test]
+    fn candidato_unico() {
+        assert_eq!(nomes("  st"), ("st", vec!["status"]));
+        assert_eq!(nomes("set kd"), ("kd", vec!["kd"]));
+        assert_eq!(nomes("modo p"), ("p", vec!["parado"]));
+        assert_eq!(prefixo_comum(&["status"]), "status");
+    }
+
+    #[test]
+    fn varios_com_prefixo_comum() {
+        let (palavra, encontrados) = nomes("se");
+        assert_eq!((palavra, encontrados.as_slice()), ("se", ["set", "sensores"].as_slice()));
+        assert_eq!(prefixo_comum(&encontrados), "se");
+        // Palavra vazia lista tudo, help incluído
+        assert_eq!(nomes("").1.len(), COMANDOS.len() + 1);
+        assert_eq!(prefixo_comum(&nomes("set k").1), "k");
+        assert_eq!(prefixo_comum(&["sensores", "sensor", "sens"]), "sens");
+    }
+
+    #[test]
+    fn nome_completo() {
+        // "modo" já é um comando mas também prefixo de "modos"
+        let (_, encontrados) = nomes("modo");
+        assert_eq!(encontrados, ["modo", "modos"]);
+        assert_eq!(prefixo_comum(&encontrados), "modo");
+        assert_eq!(nomes("help"), ("help", vec!["help"]));
+    }
+
+    #[test]
+    fn separadores() {
+        assert_eq!(divide_comando("  vel 300 "), ("vel", "300"));
+        assert_eq!(divide_comando("voltas=3"), ("voltas", "3"));
+        assert_eq!(divide_comando("set kp 1.5"), ("set", "kp 1.5"));
+        // Só o primeiro separador divide
+        assert_eq!(divide_comando("set kp=1.5"), ("set", "kp=1.5"));
+        assert_eq!(divide_comando("status"), ("status", ""));
+        assert_eq!(divide_comando(""), ("", ""));
+    }
+}
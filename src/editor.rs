@@ -0,0 +1,483 @@
+// Editor de linha do console para terminais VT100
+//
+// Recebe um byte por vez e devolve o eco (sequências de controle incluídas)
+// em saida(), sem depender da UART: o console_shell só repassa os bytes.
+
+use core::fmt::Write;
+use heapless::{Deque, Vec};
+
+pub const TAMANHO_LINHA: usize = 64;
+// Comandos guardados no histórico (os mais antigos são descartados)
+pub const TAMANHO_HISTORICO: usize = 8;
+// Pior caso: redesenhar a linha inteira mais as sequências de cursor
+const TAMANHO_SAIDA: usize = TAMANHO_LINHA + 32;
+
+const CTRL_C: u8 = 0x03;
+const BACKSPACE: u8 = 0x08;
+const TAB: u8 = 0x09;
+const ESC: u8 = 0x1b;
+const DEL: u8 = 0x7f;
+
+type Linha = Vec<u8, TAMANHO_LINHA>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum EventoLinha {
+    // Enter: a linha digitada fica em linha() até o próximo byte
+    Pronta,
+    // Ctrl-C descartou a linha
+    Cancelada,
+    // Pedido de completar a palavra antes do cursor, tratado por quem conhece os comandos
+    Tab,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    Nenhum,
+    // Recebeu ESC
+    Inicio,
+    // ESC [ com o primeiro parâmetro numérico e se ele ainda está sendo lido;
+    // o que vem depois do ';' (modificadores) é consumido e ignorado
+    Csi(u8, bool),
+    // ESC O (teclas home/end de alguns terminais)
+    Ss3,
+}
+
+pub struct EditorLinha {
+    linha: Linha,
+    cursor: usize,
+    escape: Escape,
+    saida: Vec<u8, TAMANHO_SAIDA>,
+    historico: Deque<Linha, TAMANHO_HISTORICO>,
+    // Posição no histórico contando do mais recente; None editando a linha nova
+    navegando: Option<usize>,
+    // Linha que estava sendo digitada antes de subir no histórico
+    rascunho: Linha,
+    pronta: bool,
+    ultimo_cr: bool,
+}
+
+impl EditorLinha {
+    pub const fn new() -> Self {
+        Self {
+            linha: Vec::new(),
+            cursor: 0,
+            escape: Escape::Nenhum,
+            saida: Vec::new(),
+            historico: Deque::new(),
+            navegando: None,
+            rascunho: Vec::new(),
+            pronta: false,
+            ultimo_cr: false,
+        }
+    }
+
+    // Só entram caracteres ASCII imprimíveis, então a linha é sempre UTF-8 válido
+    pub fn linha(&self) -> &str {
+        core::str::from_utf8(&self.linha).unwrap_or("")
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn ate_cursor(&self) -> &str {
+        &self.linha()[..self.cursor]
+    }
+
+    // Eco do último byte processado, a ser enviado ao terminal
+    pub fn saida(&self) -> &[u8] {
+        &self.saida
+    }
+
+    pub fn historico(&self) -> impl Iterator<Item = &str> {
+        self.historico.iter().map(|l| core::str::from_utf8(l).unwrap_or(""))
+    }
+
+    pub fn processa(&mut self, byte: u8) -> Option<EventoLinha> {
+        self.saida.clear();
+
+        // Terminais que mandam CR LF no Enter geram uma linha só
+        let ultimo_cr = core::mem::replace(&mut self.ultimo_cr, byte == b'\r');
+        if byte == b'\n' && ultimo_cr {
+            return None;
+        }
+
+        if self.pronta {
+            self.pronta = false;
+            self.linha.clear();
+            self.cursor = 0;
+        }
+
+        match self.escape {
+            Escape::Nenhum => {}
+            Escape::Inicio => {
+                self.escape = match byte {
+                    b'[' => Escape::Csi(0, true),
+                    b'O' => Escape::Ss3,
+                    _ => Escape::Nenhum,
+                };
+                return None;
+            }
+            Escape::Csi(parametro, lendo) => {
+                self.escape = Escape::Nenhum;
+                match byte {
+                    b'0'..=b'9' if lendo => {
+                        let parametro = parametro.saturating_mul(10).saturating_add(byte - b'0');
+                        self.escape = Escape::Csi(parametro, true);
+                    }
+                    // Demais bytes de parâmetro e intermediários até o byte final
+                    0x20..=0x3f => self.escape = Escape::Csi(parametro, false),
+                    b'A' => self.historico_anterior(),
+                    b'B' => self.historico_seguinte(),
+                    b'C' => self.direita(),
+                    b'D' => self.esquerda(),
+                    b'H' => self.inicio(),
+                    b'F' => self.fim(),
+                    b'~' => match parametro {
+                        1 | 7 => self.inicio(),
+                        4 | 8 => self.fim(),
+                        3 => self.apaga_no_cursor(),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                return None;
+            }
+            Escape::Ss3 => {
+                self.escape = Escape::Nenhum;
+                match byte {
+                    b'H' => self.inicio(),
+                    b'F' => self.fim(),
+                    _ => {}
+                }
+                return None;
+            }
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                self.escreve(b"\r\n");
+                self.guarda_historico();
+                self.navegando = None;
+                self.pronta = true;
+                Some(EventoLinha::Pronta)
+            }
+            CTRL_C => {
+                self.escreve(b"^C\r\n");
+                self.navegando = None;
+                self.pronta = true;
+                Some(EventoLinha::Cancelada)
+            }
+            ESC => {
+                self.escape = Escape::Inicio;
+                None
+            }
+            TAB => Some(EventoLinha::Tab),
+            BACKSPACE | DEL => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.linha.remove(self.cursor);
+                    self.escreve(b"\x08");
+                    self.redesenha_cauda();
+                }
+                None
+            }
+            0x20..=0x7e => {
+                self.insere(byte);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // Insere texto no cursor (completar comando); o eco substitui a saída atual
+    pub fn insere_texto(&mut self, texto: &str) {
+        self.saida.clear();
+        let inicio = self.cursor;
+        for byte in texto.bytes().filter(|b| (0x20..=0x7e).contains(b)) {
+            if self.linha.insert(self.cursor, byte).is_err() {
+                break;
+            }
+            self.cursor += 1;
+        }
+        self.escreve_linha(inicio);
+        self.move_esquerda(self.linha.len() - self.cursor);
+    }
+
+    // Escreve a linha inteira de novo, depois de outro texto ter sido impresso abaixo do prompt
+    pub fn redesenha(&mut self) {
+        self.saida.clear();
+        self.escreve_linha(0);
+        self.move_esquerda(self.linha.len() - self.cursor);
+    }
+
+    fn insere(&mut self, byte: u8) {
+        if self.linha.insert(self.cursor, byte).is_err() {
+            return;
+        }
+        self.cursor += 1;
+        // Reescreve do caractere novo em diante e volta o cursor
+        let inicio = self.cursor - 1;
+        self.escreve_linha(inicio);
+        self.move_esquerda(self.linha.len() - self.cursor);
+    }
+
+    fn apaga_no_cursor(&mut self) {
+        if self.cursor < self.linha.len() {
+            self.linha.remove(self.cursor);
+            self.redesenha_cauda();
+        }
+    }
+
+    fn esquerda(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.move_esquerda(1);
+        }
+    }
+
+    fn direita(&mut self) {
+        if self.cursor < self.linha.len() {
+            self.cursor += 1;
+            self.move_direita(1);
+        }
+    }
+
+    fn inicio(&mut self) {
+        self.move_esquerda(self.cursor);
+        self.cursor = 0;
+    }
+
+    fn fim(&mut self) {
+        self.move_direita(self.linha.len() - self.cursor);
+        self.cursor = self.linha.len();
+    }
+
+    fn historico_anterior(&mut self) {
+        let indice = self.navegando.map_or(0, |i| i + 1);
+        let Some(anterior) = self.historico.iter().rev().nth(indice).cloned() else {
+            return;
+        };
+        if self.navegando.is_none() {
+            self.rascunho = self.linha.clone();
+        }
+        self.navegando = Some(indice);
+        self.substitui(anterior);
+    }
+
+    fn historico_seguinte(&mut self) {
+        match self.navegando {
+            None => {}
+            Some(0) => {
+                self.navegando = None;
+                let rascunho = core::mem::take(&mut self.rascunho);
+                self.substitui(rascunho);
+            }
+            Some(i) => {
+                self.navegando = Some(i - 1);
+                if let Some(seguinte) = self.historico.iter().rev().nth(i - 1).cloned() {
+                    self.substitui(seguinte);
+                }
+            }
+        }
+    }
+
+    fn guarda_historico(&mut self) {
+        if self.linha.is_empty() || self.historico.back() == Some(&self.linha) {
+            return;
+        }
+        if self.historico.is_full() {
+            self.historico.pop_front();
+        }
+        let _ = self.historico.push_back(self.linha.clone());
+    }
+
+    // Troca a linha inteira mantendo o que foi escrito antes dela no terminal (o prompt)
+    fn substitui(&mut self, linha: Linha) {
+        self.move_esquerda(self.cursor);
+        self.linha = linha;
+        self.cursor = self.linha.len();
+        self.escreve_linha(0);
+        self.escreve(b"\x1b[K");
+    }
+
+    // Depois de remover um caractere: reescreve do cursor ao fim, limpa a sobra e volta
+    fn redesenha_cauda(&mut self) {
+        self.escreve_linha(self.cursor);
+        self.escreve(b"\x1b[K");
+        self.move_esquerda(self.linha.len() - self.cursor);
+    }
+
+    fn escreve_linha(&mut self, inicio: usize) {
+        let _ = self.saida.extend_from_slice(&self.linha[inicio..]);
+    }
+
+    fn escreve(&mut self, bytes: &[u8]) {
+        let _ = self.saida.extend_from_slice(bytes);
+    }
+
+    fn move_esquerda(&mut self, n: usize) {
+        if n > 0 {
+            let _ = write!(self.saida, "\x1b[{}D", n);
+        }
+    }
+
+    fn move_direita(&mut self, n: usize) {
+        if n > 0 {
+            let _ = write!(self.saida, "\x1b[{}C", n);
+        }
+    }
+}
+
+impl Default for EditorLinha {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ESQUERDA: &[u8] = b"\x1b[D";
+    const DIREITA: &[u8] = b"\x1b[C";
+    const CIMA: &[u8] = b"\x1b[A";
+    const BAIXO: &[u8] = b"\x1b[B";
+
+    // Processa os bytes em ordem e devolve o último evento
+    fn digita(editor: &mut EditorLinha, bytes: &[u8]) -> Option<EventoLinha> {
+        bytes.iter().fold(None, |_, &b| editor.processa(b))
+    }
+
+    #[test]
+    fn insere_e_apaga_no_meio() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"sttus");
+        for _ in 0..3 {
+            digita(&mut e, ESQUERDA);
+        }
+        digita(&mut e, b"a");
+        assert_eq!((e.linha(), e.cursor()), ("status", 3));
+        // Eco: do caractere novo ao fim e volta o cursor até ele
+        assert_eq!(e.saida(), b"atus\x1b[3D");
+
+        digita(&mut e, DIREITA);
+        digita(&mut e, &[DEL]);
+        assert_eq!((e.linha(), e.cursor()), ("staus", 3));
+        assert_eq!(e.saida(), b"\x08us\x1b[K\x1b[2D");
+
+        assert_eq!(digita(&mut e, b"\r"), Some(EventoLinha::Pronta));
+        assert_eq!(e.linha(), "staus");
+    }
+
+    #[test]
+    fn setas_nas_bordas_e_home_end() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"ab");
+        digita(&mut e, DIREITA);
+        assert_eq!(e.cursor(), 2);
+        assert!(e.saida().is_empty());
+        digita(&mut e, b"\x1b[H");
+        assert_eq!(e.cursor(), 0);
+        digita(&mut e, ESQUERDA);
+        assert_eq!(e.cursor(), 0);
+        digita(&mut e, b"\x1b[4~");
+        assert_eq!(e.cursor(), 2);
+        digita(&mut e, b"\x1bOH");
+        assert_eq!(e.cursor(), 0);
+    }
+
+    #[test]
+    fn historico() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"status\r");
+        digita(&mut e, b"mem\r\n");
+        // Repetido em seguida não entra de novo
+        digita(&mut e, b"mem\r");
+        assert_eq!(e.historico().collect::<std::vec::Vec<_>>(), ["status", "mem"]);
+
+        digita(&mut e, b"ta");
+        digita(&mut e, CIMA);
+        assert_eq!(e.linha(), "mem");
+        digita(&mut e, CIMA);
+        assert_eq!(e.linha(), "status");
+        // Além do mais antigo não muda
+        digita(&mut e, CIMA);
+        assert_eq!(e.linha(), "status");
+        digita(&mut e, BAIXO);
+        assert_eq!(e.linha(), "mem");
+        // Voltando ao fim recupera o que estava sendo digitado
+        digita(&mut e, BAIXO);
+        assert_eq!((e.linha(), e.cursor()), ("ta", 2));
+    }
+
+    #[test]
+    fn historico_descarta_os_mais_antigos() {
+        let mut e = EditorLinha::new();
+        for i in 0..TAMANHO_HISTORICO + 2 {
+            digita(&mut e, &[b'a' + i as u8, b'\r']);
+        }
+        let historico: std::vec::Vec<_> = e.historico().collect();
+        assert_eq!(historico.len(), TAMANHO_HISTORICO);
+        assert_eq!(historico[0], "c");
+    }
+
+    #[test]
+    fn linha_cheia() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, &[b'x'; TAMANHO_LINHA + 5]);
+        assert_eq!(e.linha().len(), TAMANHO_LINHA);
+        assert_eq!(e.cursor(), TAMANHO_LINHA);
+        // Sem eco para o que não coube
+        assert!(e.saida().is_empty());
+        e.insere_texto("abc");
+        assert_eq!(e.linha().len(), TAMANHO_LINHA);
+    }
+
+    #[test]
+    fn escape_em_partes() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"abc");
+        // A sequência chega um byte por vez, sem eco nem inserção no meio
+        assert_eq!(e.processa(ESC), None);
+        assert_eq!(e.processa(b'['), None);
+        assert!(e.saida().is_empty());
+        assert_eq!(e.processa(b'3'), None);
+        assert_eq!(e.processa(b'~'), None);
+        assert_eq!(e.linha(), "abc");
+        digita(&mut e, ESQUERDA);
+        digita(&mut e, b"\x1b[3~");
+        assert_eq!((e.linha(), e.cursor()), ("ab", 2));
+        // Sequência desconhecida é ignorada por inteiro
+        digita(&mut e, b"\x1b[Z");
+        assert_eq!(e.linha(), "ab");
+    }
+
+    #[test]
+    fn escape_com_modificadores() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"abc");
+        // Ctrl+seta esquerda: o ';' e o modificador não entram na linha
+        digita(&mut e, b"\x1b[1;5D");
+        assert_eq!((e.linha(), e.cursor()), ("abc", 2));
+        assert_eq!(e.saida(), b"\x1b[1D");
+        // Ctrl+Delete ainda apaga pelo primeiro parâmetro
+        digita(&mut e, b"\x1b[3;5~");
+        assert_eq!((e.linha(), e.cursor()), ("ab", 2));
+        // Com byte intermediário a sequência é consumida até o byte final
+        digita(&mut e, b"\x1b[2 q");
+        assert_eq!(e.linha(), "ab");
+    }
+
+    #[test]
+    fn ctrl_c_e_tab() {
+        let mut e = EditorLinha::new();
+        digita(&mut e, b"mo");
+        assert_eq!(e.processa(TAB), Some(EventoLinha::Tab));
+        assert_eq!(e.ate_cursor(), "mo");
+        assert_eq!(e.processa(CTRL_C), Some(EventoLinha::Cancelada));
+        digita(&mut e, b"x");
+        assert_eq!(e.linha(), "x");
+        assert_eq!(e.historico().count(), 0);
+    }
+}
@@ -0,0 +1,138 @@
+// Contagem dos encoders de quadratura das rodas
+//
+// O timer em modo encoder conta em 16 bits; aqui a contagem é estendida
+// para 32 bits com sinal e convertida em velocidade.
+
+// Pulsos por volta da roda já em quadratura x4 (12 CPR x redução 30:1 x 4)
+pub const TICKS_POR_VOLTA: u32 = 1440;
+
+pub const DIAMETRO_RODA_MM: f32 = 32.0;
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct GeometriaRoda {
+    pub ticks_por_volta: u32,
+    pub diametro_mm: f32,
+}
+
+impl GeometriaRoda {
+    pub const fn new(ticks_por_volta: u32, diametro_mm: f32) -> Self {
+        Self { ticks_por_volta, diametro_mm }
+    }
+
+    pub fn mm_por_tick(&self) -> f32 {
+        core::f32::consts::PI * self.diametro_mm / self.ticks_por_volta as f32
+    }
+}
+
+impl Default for GeometriaRoda {
+    fn default() -> Self {
+        Self::new(TICKS_POR_VOLTA, DIAMETRO_RODA_MM)
+    }
+}
+
+// Estende a contagem de 16 bits do timer. Precisa ser atualizado antes que a
+// roda ande meia volta do contador (32768 ticks) entre duas leituras.
+pub struct ContadorEstendido {
+    ultima_leitura: Option<u16>,
+    total: i32,
+    // Roda montada espelhada conta ao contrário
+    invertido: bool,
+}
+
+impl ContadorEstendido {
+    pub const fn new(invertido: bool) -> Self {
+        Self {
+            ultima_leitura: None,
+            total: 0,
+            invertido,
+        }
+    }
+
+    // Recebe o valor bruto do contador e devolve a variação desde a última leitura
+    pub fn atualiza(&mut self, contagem: u16) -> i32 {
+        let delta = match self.ultima_leitura {
+            // A diferença em complemento de 2 trata o estouro nos dois sentidos
+            Some(anterior) => contagem.wrapping_sub(anterior) as i16 as i32,
+            None => 0,
+        };
+        self.ultima_leitura = Some(contagem);
+
+        let delta = if self.invertido { -delta } else { delta };
+        self.total = self.total.wrapping_add(delta);
+        delta
+    }
+
+    pub fn total(&self) -> i32 {
+        self.total
+    }
+
+    pub fn zera(&mut self) {
+        self.total = 0;
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug, defmt::Format)]
+pub struct VelocidadeRoda {
+    pub ticks_s: i32,
+    pub mm_s: f32,
+}
+
+// Velocidade a partir dos ticks contados em um período fixo de amostragem
+pub fn calcula_velocidade(delta_ticks: i32, periodo_s: f32, geometria: &GeometriaRoda) -> VelocidadeRoda {
+    if periodo_s <= 0.0 {
+        return VelocidadeRoda::default();
+    }
+    let ticks_s = delta_ticks as f32 / periodo_s;
+    VelocidadeRoda {
+        ticks_s: ticks_s as i32,
+        mm_s: ticks_s * geometria.mm_por_tick(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estouro_para_frente() {
+        let mut c = ContadorEstendido::new(false);
+        assert_eq!(c.atualiza(0xFFF0), 0);
+        assert_eq!(c.atualiza(0x0010), 32);
+        assert_eq!(c.atualiza(0x0020), 16);
+        assert_eq!(c.total(), 48);
+    }
+
+    #[test]
+    fn estouro_para_tras() {
+        let mut c = ContadorEstendido::new(false);
+        c.atualiza(0x0010);
+        assert_eq!(c.atualiza(0xFFF0), -32);
+        assert_eq!(c.total(), -32);
+    }
+
+    #[test]
+    fn invertido_nos_dois_sentidos() {
+        let mut c = ContadorEstendido::new(true);
+        c.atualiza(0xFFF0);
+        assert_eq!(c.atualiza(0x0010), -32);
+        assert_eq!(c.atualiza(0xFFF0), 32);
+        assert_eq!(c.total(), 0);
+
+        // 0 -> 0xFFFF é um tick para trás, que a roda espelhada conta como para frente
+        let mut c = ContadorEstendido::new(true);
+        c.atualiza(0x0000);
+        assert_eq!(c.atualiza(0xFFFF), 1);
+        assert_eq!(c.total(), 1);
+        c.zera();
+        assert_eq!(c.total(), 0);
+    }
+
+    #[test]
+    fn velocidade() {
+        let geometria = GeometriaRoda::new(1000, 1000.0 / core::f32::consts::PI);
+        let v = calcula_velocidade(50, 0.01, &geometria);
+        assert_eq!(v.ticks_s, 5000);
+        assert!((v.mm_s - 5000.0).abs() < 0.1);
+        assert_eq!(calcula_velocidade(50, 0.0, &geometria), VelocidadeRoda::default());
+    }
+}
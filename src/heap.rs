@@ -0,0 +1,139 @@
+// Alocador global opcional (feature "heap")
+//
+// Usa o linked_list_allocator sobre uma região estática de tamanho fixo e
+// conta o uso para o comando heap: a lista livre já sabe quanto está em uso,
+// aqui ficam só o pico e as falhas.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use linked_list_allocator::Heap;
+
+use crate::compartilhado::Compartilhado;
+
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct UsoHeap {
+    pub total: usize,
+    pub usado: usize,
+    pub livre: usize,
+    pub pico: usize,
+    pub alocacoes: u32,
+    // Pedidos que não couberam (o alloc devolveu nulo)
+    pub falhas: u32,
+}
+
+struct EstadoHeap {
+    heap: Heap,
+    pico: usize,
+    alocacoes: u32,
+    falhas: u32,
+}
+
+pub struct Alocador {
+    estado: Compartilhado<EstadoHeap>,
+}
+
+impl Alocador {
+    pub const fn new() -> Self {
+        Self {
+            estado: Compartilhado::new(EstadoHeap {
+                heap: Heap::empty(),
+                pico: 0,
+                alocacoes: 0,
+                falhas: 0,
+            }),
+        }
+    }
+
+    // Deve ser chamado uma vez, antes da primeira alocação; até lá toda alocação falha
+    pub fn inicia(&self, regiao: &'static mut [MaybeUninit<u8>]) {
+        self.estado.atualiza(|e| e.heap.init_from_slice(regiao));
+    }
+
+    pub fn uso(&self) -> UsoHeap {
+        self.estado.com(|e| UsoHeap {
+            total: e.heap.size(),
+            usado: e.heap.used(),
+            livre: e.heap.free(),
+            pico: e.pico,
+            alocacoes: e.alocacoes,
+            falhas: e.falhas,
+        })
+    }
+}
+
+impl Default for Alocador {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Alocador {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.estado.atualiza(|e| match e.heap.allocate_first_fit(layout) {
+            Ok(p) => {
+                e.alocacoes = e.alocacoes.wrapping_add(1);
+                e.pico = e.pico.max(e.heap.used());
+                p.as_ptr()
+            }
+            Err(()) => {
+                e.falhas = e.falhas.wrapping_add(1);
+                ptr::null_mut()
+            }
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(p) = NonNull::new(ptr) {
+            self.estado.atualiza(|e| e.heap.deallocate(p, layout));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uso_pico_e_falhas() {
+        let alocador = Alocador::new();
+        let regiao = std::boxed::Box::leak(std::boxed::Box::new([MaybeUninit::<u8>::uninit(); 1024]));
+        alocador.inicia(regiao);
+        assert_eq!(alocador.uso().total, 1024);
+        assert_eq!(alocador.uso().usado, 0);
+
+        let a = Layout::from_size_align(256, 8).unwrap();
+        let b = Layout::from_size_align(128, 8).unwrap();
+        unsafe {
+            let pa = alocador.alloc(a);
+            let pb = alocador.alloc(b);
+            assert!(!pa.is_null() && !pb.is_null());
+            let uso = alocador.uso();
+            assert_eq!((uso.usado, uso.pico, uso.alocacoes), (384, 384, 2));
+            assert_eq!(uso.livre, 1024 - 384);
+
+            // Liberar baixa o uso mas o pico fica
+            alocador.dealloc(pa, a);
+            let uso = alocador.uso();
+            assert_eq!((uso.usado, uso.pico), (128, 384));
+
+            // Maior que a região: nulo e conta a falha sem mexer no resto
+            let grande = Layout::from_size_align(2048, 8).unwrap();
+            assert!(alocador.alloc(grande).is_null());
+            let uso = alocador.uso();
+            assert_eq!((uso.usado, uso.pico, uso.alocacoes, uso.falhas), (128, 384, 2, 1));
+
+            alocador.dealloc(pb, b);
+        }
+        assert_eq!(alocador.uso().usado, 0);
+    }
+
+    #[test]
+    fn sem_inicia_toda_alocacao_falha() {
+        let alocador = Alocador::new();
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        assert!(unsafe { alocador.alloc(layout) }.is_null());
+        assert_eq!((alocador.uso().total, alocador.uso().falhas), (0, 1));
+    }
+}
@@ -0,0 +1,184 @@
+// Padrões de piscar dos LEDs de estado
+//
+// Cada padrão é uma sequência de passos (um brilho por um tempo) que se
+// repete. O Tocador devolve um passo por vez e a tarefa do LED só espera o
+// tempo do passo ou a chegada de um padrão novo.
+//
+// O brilho é o percebido, de 0 a BRILHO_MAX; a curva gama converte para o
+// ciclo ativo do PWM só na saída, para a respiração parecer uniforme.
+
+// Aceso e apagado de cada piscada de um código
+pub const PISCADA_CODIGO_MS: u32 = 200;
+// Pausa entre repetições do código, longa o bastante para contar as piscadas
+pub const PAUSA_CODIGO_MS: u32 = 1200;
+pub const BRILHO_MAX: u8 = u8::MAX;
+// Duração de cada degrau da rampa do Respira
+pub const DEGRAU_RESPIRA_MS: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Padrao {
+    Apagado,
+    Aceso,
+    // Aceso com brilho fixo, de 0 a BRILHO_MAX
+    Nivel(u8),
+    // Tempo aceso e apagado em ms
+    Pisca(u32),
+    // N piscadas e uma pausa, para códigos de erro
+    Codigo(u8),
+    // Duas batidas curtas e uma pausa longa: sistema vivo; período em ms
+    Batimento(u32),
+    // Sobe e desce o brilho devagar; período em ms
+    Respira(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub struct Passo {
+    pub brilho: u8,
+    // None: fica assim até o próximo padrão
+    pub duracao_ms: Option<u32>,
+}
+
+impl Passo {
+    const fn novo(brilho: u8, duracao_ms: u32) -> Self {
+        Self { brilho, duracao_ms: Some(duracao_ms) }
+    }
+
+    const fn fixo(brilho: u8) -> Self {
+        Self { brilho, duracao_ms: None }
+    }
+}
+
+// Brilho e parte do período em milésimos de cada passo do Batimento
+const BATIMENTO: [(u8, u32); 4] = [(BRILHO_MAX, 60), (0, 120), (BRILHO_MAX, 60), (0, 760)];
+
+// (brilho / 255) ^ 2.2 em fração de u16::MAX
+const GAMMA: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24, 32, 42, 53, 65, 79, 94, 111, 129,
+    148, 169, 192, 216, 242, 270, 299, 330, 362, 396, 432, 469, 508, 549, 591, 635,
+    681, 729, 779, 830, 883, 938, 995, 1053, 1113, 1175, 1239, 1305, 1373, 1443, 1514, 1587,
+    1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243, 2334, 2427, 2521, 2618, 2717, 2817, 2920, 3024,
+    3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934, 4057, 4182, 4309, 4438, 4570, 4703, 4838, 4976,
+    5115, 5257, 5401, 5547, 5695, 5845, 5998, 6152, 6309, 6468, 6629, 6792, 6957, 7124, 7294, 7466,
+    7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919, 9111, 9305, 9501, 9699, 9900, 10102, 10307, 10515,
+    10724, 10936, 11150, 11366, 11585, 11806, 12029, 12254, 12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140,
+    14386, 14635, 14885, 15138, 15394, 15652, 15912, 16174, 16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358,
+    18642, 18928, 19216, 19507, 19800, 20095, 20393, 20694, 20996, 21301, 21609, 21919, 22231, 22546, 22863, 23182,
+    23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826, 26168, 26512, 26858, 27207, 27558, 27912, 28268, 28627,
+    28988, 29351, 29717, 30086, 30457, 30830, 31206, 31585, 31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702,
+    35103, 35507, 35913, 36321, 36732, 37146, 37562, 37981, 38402, 38825, 39252, 39680, 40112, 40546, 40982, 41421,
+    41862, 42306, 42753, 43202, 43654, 44108, 44565, 45025, 45487, 45951, 46418, 46888, 47360, 47835, 48313, 48793,
+    49275, 49761, 50249, 50739, 51232, 51728, 52226, 52727, 53230, 53736, 54245, 54756, 55270, 55787, 56306, 56828,
+    57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097, 61642, 62190, 62741, 63295, 63851, 64410, 64971, 65535,
+];
+
+// Ciclo ativo (0 a u16::MAX) para o brilho reduzido a limite_pct % do máximo;
+// o limite é aplicado antes da curva, então 50 % parece metade do brilho
+pub fn ciclo_ativo(brilho: u8, limite_pct: u8) -> u16 {
+    let brilho = brilho as u32 * limite_pct.min(100) as u32 / 100;
+    GAMMA[brilho as usize]
+}
+
+pub struct Tocador {
+    padrao: Padrao,
+    passo: usize,
+}
+
+impl Tocador {
+    pub const fn new(padrao: Padrao) -> Self {
+        Self { padrao, passo: 0 }
+    }
+
+    pub fn padrao(&self) -> Padrao {
+        self.padrao
+    }
+
+    // Recomeça do primeiro passo, mesmo se o padrão for o mesmo
+    pub fn troca(&mut self, padrao: Padrao) {
+        self.padrao = padrao;
+        self.passo = 0;
+    }
+
+    pub fn proximo(&mut self) -> Passo {
+        let (passo, total) = match self.padrao {
+            Padrao::Apagado => (Passo::fixo(0), 1),
+            Padrao::Aceso => (Passo::fixo(BRILHO_MAX), 1),
+            Padrao::Nivel(brilho) => (Passo::fixo(brilho), 1),
+            Padrao::Pisca(ms) => (Passo::novo(if self.passo == 0 { BRILHO_MAX } else { 0 }, ms.max(1)), 2),
+            Padrao::Codigo(n) => {
+                // Piscadas nos passos pares e a pausa no último
+                let total = 2 * n as usize + 1;
+                if self.passo == total - 1 {
+                    (Passo::novo(0, PAUSA_CODIGO_MS), total)
+                } else {
+                    let brilho = if self.passo & 1 == 0 { BRILHO_MAX } else { 0 };
+                    (Passo::novo(brilho, PISCADA_CODIGO_MS), total)
+                }
+            }
+            Padrao::Batimento(periodo_ms) => {
+                let (brilho, milesimos) = BATIMENTO[self.passo];
+                (Passo::novo(brilho, (periodo_ms * milesimos / 1000).max(1)), BATIMENTO.len())
+            }
+            Padrao::Respira(periodo_ms) => {
+                // Rampa triangular: sobe na primeira metade dos degraus e desce na segunda
+                let total = (periodo_ms / DEGRAU_RESPIRA_MS).max(2) as usize;
+                let fase = self.passo * 2 * BRILHO_MAX as usize / total;
+                let brilho = if fase <= BRILHO_MAX as usize { fase } else { 2 * BRILHO_MAX as usize - fase };
+                (Passo::novo(brilho as u8, DEGRAU_RESPIRA_MS), total)
+            }
+        };
+        self.passo = (self.passo + 1) % total;
+        passo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn codigo() {
+        let mut t = Tocador::new(Padrao::Codigo(2));
+        let passos: Vec<_> = (0..6).map(|_| t.proximo()).collect();
+        let acesos: Vec<_> = passos[..4].iter().map(|p| p.brilho == BRILHO_MAX).collect();
+        assert_eq!(acesos, [true, false, true, false]);
+        assert_eq!(passos[4], Passo { brilho: 0, duracao_ms: Some(PAUSA_CODIGO_MS) });
+        assert_eq!(passos[5], passos[0]);
+    }
+
+    #[test]
+    fn pisca_e_batimento_seguem_o_ritmo() {
+        let mut t = Tocador::new(Padrao::Aceso);
+        assert_eq!(t.proximo().duracao_ms, None);
+        t.troca(Padrao::Pisca(100));
+        assert_eq!(t.proximo(), Passo { brilho: BRILHO_MAX, duracao_ms: Some(100) });
+        assert_eq!(t.proximo(), Passo { brilho: 0, duracao_ms: Some(100) });
+
+        for periodo in [1000, 2000] {
+            t.troca(Padrao::Batimento(periodo));
+            let passos: Vec<_> = (0..4).map(|_| t.proximo()).collect();
+            assert_eq!(passos.iter().map(|p| p.duracao_ms.unwrap()).sum::<u32>(), periodo);
+            assert_eq!(passos[0].duracao_ms, Some(periodo * 60 / 1000));
+        }
+    }
+
+    #[test]
+    fn respira() {
+        let mut t = Tocador::new(Padrao::Respira(1000));
+        let brilhos: Vec<_> = (0..50).map(|_| t.proximo().brilho).collect();
+        assert_eq!(brilhos[0], 0);
+        assert_eq!(brilhos[25], BRILHO_MAX);
+        assert!(brilhos[..26].windows(2).all(|w| w[0] < w[1]));
+        assert!(brilhos[25..].windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(t.proximo().brilho, 0);
+    }
+
+    #[test]
+    fn gamma() {
+        assert_eq!(ciclo_ativo(0, 100), 0);
+        assert_eq!(ciclo_ativo(BRILHO_MAX, 100), u16::MAX);
+        assert!(ciclo_ativo(128, 100) < u16::MAX / 4);
+        assert_eq!(ciclo_ativo(BRILHO_MAX, 50), ciclo_ativo(127, 100));
+        assert_eq!(ciclo_ativo(BRILHO_MAX, 0), 0);
+    }
+}
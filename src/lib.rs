@@ -0,0 +1,29 @@
+#![cfg_attr(not(test), no_std)]
+
+// Módulos compartilhados pelos binários em src/bin.
+// A lógica que não depende do hardware fica aqui para poder ser
+// testada no host com leituras sintéticas.
+
+pub mod botao;
+pub mod compartilhado;
+pub mod console;
+pub mod editor;
+pub mod encoder;
+#[cfg(feature = "heap")]
+pub mod heap;
+pub mod leds;
+pub mod mapa;
+pub mod marcadores;
+pub mod memoria;
+pub mod modo;
+pub mod motores;
+pub mod odometria;
+pub mod parametros;
+pub mod pid;
+// Driver da ponte H, que depende do embassy-stm32
+#[cfg(target_os = "none")]
+pub mod ponte;
+pub mod sensores;
+pub mod tarefas;
+pub mod tempo_real;
+pub mod velocidade;
@@ -0,0 +1,509 @@
+// Aprendizado da pista: curvatura x distância na volta lenta, segmentação em
+// retas e curvas e perfil de velocidade para as voltas seguintes
+
+use heapless::Vec;
+// Nos testes do host a std já traz as funções de f32 e o import fica sem uso
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+pub const MAX_TRECHOS: usize = 64;
+
+// Distância entre duas amostras de curvatura
+pub const PASSO_AMOSTRA_MM: f32 = 20.0;
+
+// Curvatura (1/raio) acima da qual a amostra é curva: raio menor que 600 mm
+pub const LIMIAR_CURVATURA: f32 = 1.0 / 600.0;
+
+// Correção do PID (mm/s de diferença entre as rodas) acima da qual a amostra é curva
+pub const LIMIAR_CORRECAO: f32 = 150.0;
+
+// Trechos mais curtos que isso são ruído e são absorvidos pelo trecho anterior
+pub const TRECHO_MINIMO_MM: f32 = 80.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum TipoTrecho {
+    Reta,
+    Curva,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct Trecho {
+    pub tipo: TipoTrecho,
+    pub inicio_mm: f32,
+    pub fim_mm: f32,
+    // Maior |curvatura| vista no trecho, em 1/mm
+    pub curvatura_max: f32,
+}
+
+impl Trecho {
+    pub fn comprimento_mm(&self) -> f32 {
+        self.fim_mm - self.inicio_mm
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum ErroMapa {
+    Cheio,
+    BufferPequeno,
+    SemMapa,
+    Corrompido,
+}
+
+pub struct MapaPista {
+    trechos: Vec<Trecho, MAX_TRECHOS>,
+    comprimento_mm: f32,
+}
+
+impl MapaPista {
+    pub const fn new() -> Self {
+        Self {
+            trechos: Vec::new(),
+            comprimento_mm: 0.0,
+        }
+    }
+
+    pub fn limpa(&mut self) {
+        self.trechos.clear();
+        self.comprimento_mm = 0.0;
+    }
+
+    pub fn trechos(&self) -> &[Trecho] {
+        &self.trechos
+    }
+
+    pub fn comprimento_mm(&self) -> f32 {
+        self.comprimento_mm
+    }
+
+    pub fn valido(&self) -> bool {
+        !self.trechos.is_empty() && self.comprimento_mm > 0.0
+    }
+
+    // Distância dentro da volta atual, em 0..comprimento
+    pub fn na_volta(&self, distancia_mm: f32) -> f32 {
+        let distancia = distancia_mm % self.comprimento_mm;
+        if distancia < 0.0 {
+            distancia + self.comprimento_mm
+        } else {
+            distancia
+        }
+    }
+
+    // Índice do trecho que contém a distância medida desde o início da volta
+    pub fn indice_em(&self, distancia_mm: f32) -> Option<usize> {
+        if !self.valido() {
+            return None;
+        }
+        let distancia = self.na_volta(distancia_mm);
+        self.trechos
+            .iter()
+            .position(|t| distancia < t.fim_mm)
+            .or(Some(self.trechos.len() - 1))
+    }
+
+    // Distância até o início da próxima curva, dando a volta na pista se preciso
+    pub fn distancia_ate_curva(&self, distancia_mm: f32) -> Option<f32> {
+        let atual = self.indice_em(distancia_mm)?;
+        let distancia = self.na_volta(distancia_mm);
+        let n = self.trechos.len();
+
+        (1..=n).find_map(|k| {
+            let t = &self.trechos[(atual + k) % n];
+            if t.tipo != TipoTrecho::Curva {
+                return None;
+            }
+            let volta = if atual + k >= n { self.comprimento_mm } else { 0.0 };
+            Some(t.inicio_mm + volta - distancia)
+        })
+    }
+
+    // Junta trechos curtos ao anterior e trechos vizinhos do mesmo tipo
+    fn consolida(&mut self) {
+        let mut consolidado: Vec<Trecho, MAX_TRECHOS> = Vec::new();
+
+        for trecho in self.trechos.iter() {
+            match consolidado.last_mut() {
+                Some(anterior) if anterior.tipo == trecho.tipo || trecho.comprimento_mm() < TRECHO_MINIMO_MM => {
+                    anterior.fim_mm = trecho.fim_mm;
+                    anterior.curvatura_max = anterior.curvatura_max.max(trecho.curvatura_max);
+                }
+                _ => {
+                    // Cabe: consolidado nunca tem mais trechos que o original
+                    let _ = consolidado.push(*trecho);
+                }
+            }
+        }
+        self.trechos = consolidado;
+    }
+
+    // Formato: MAGIA, quantidade, comprimento, soma de verificação e os trechos
+    // em little-endian (inicio, fim, curvatura em f32 e o tipo em u32). A soma
+    // cobre quantidade, comprimento e trechos
+    pub fn serializa(&self, buf: &mut [u8]) -> Result<usize, ErroMapa> {
+        let tamanho = TAMANHO_CABECALHO + self.trechos.len() * TAMANHO_TRECHO;
+        if buf.len() < tamanho {
+            return Err(ErroMapa::BufferPequeno);
+        }
+
+        for (i, t) in self.trechos.iter().enumerate() {
+            let b = &mut buf[TAMANHO_CABECALHO + i * TAMANHO_TRECHO..][..TAMANHO_TRECHO];
+            b[0..4].copy_from_slice(&t.inicio_mm.to_le_bytes());
+            b[4..8].copy_from_slice(&t.fim_mm.to_le_bytes());
+            b[8..12].copy_from_slice(&t.curvatura_max.to_le_bytes());
+            let tipo: u32 = match t.tipo {
+                TipoTrecho::Reta => 0,
+                TipoTrecho::Curva => 1,
+            };
+            b[12..16].copy_from_slice(&tipo.to_le_bytes());
+        }
+
+        buf[0..4].copy_from_slice(&MAGIA_MAPA.to_le_bytes());
+        buf[4..8].copy_from_slice(&(self.trechos.len() as u32).to_le_bytes());
+        buf[8..12].copy_from_slice(&self.comprimento_mm.to_le_bytes());
+        let soma = soma_verificacao(&buf[4..12], &buf[TAMANHO_CABECALHO..tamanho]);
+        buf[12..16].copy_from_slice(&soma.to_le_bytes());
+
+        Ok(tamanho)
+    }
+
+    pub fn desserializa(buf: &[u8]) -> Result<Self, ErroMapa> {
+        let le_u32 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        let le_f32 = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+
+        if buf.len() < TAMANHO_CABECALHO || le_u32(&buf[0..4]) != MAGIA_MAPA {
+            return Err(ErroMapa::SemMapa);
+        }
+        // Quantidade conferida antes de calcular o tamanho, que estouraria em 32 bits
+        let quantidade = le_u32(&buf[4..8]) as usize;
+        if quantidade > MAX_TRECHOS {
+            return Err(ErroMapa::Corrompido);
+        }
+        let tamanho = TAMANHO_CABECALHO + quantidade * TAMANHO_TRECHO;
+        if buf.len() < tamanho {
+            return Err(ErroMapa::Corrompido);
+        }
+        if soma_verificacao(&buf[4..12], &buf[TAMANHO_CABECALHO..tamanho]) != le_u32(&buf[12..16]) {
+            return Err(ErroMapa::Corrompido);
+        }
+
+        let mut mapa = MapaPista::new();
+        mapa.comprimento_mm = le_f32(&buf[8..12]);
+        for i in 0..quantidade {
+            let b = &buf[TAMANHO_CABECALHO + i * TAMANHO_TRECHO..][..TAMANHO_TRECHO];
+            let tipo = match le_u32(&b[12..16]) {
+                0 => TipoTrecho::Reta,
+                1 => TipoTrecho::Curva,
+                _ => return Err(ErroMapa::Corrompido),
+            };
+            let trecho = Trecho {
+                tipo,
+                inicio_mm: le_f32(&b[0..4]),
+                fim_mm: le_f32(&b[4..8]),
+                curvatura_max: le_f32(&b[8..12]),
+            };
+            // NaN passaria pela comparação de comprimento abaixo e chegaria ao perfil de velocidade
+            if ![trecho.inicio_mm, trecho.fim_mm, trecho.curvatura_max].iter().all(|v| v.is_finite()) {
+                return Err(ErroMapa::Corrompido);
+            }
+            let _ = mapa.trechos.push(trecho);
+        }
+
+        // Os trechos cobrem a volta inteira sem buracos
+        let soma_trechos: f32 = mapa.trechos.iter().map(Trecho::comprimento_mm).sum();
+        if !mapa.comprimento_mm.is_finite() || (soma_trechos - mapa.comprimento_mm).abs() > TOLERANCIA_COMPRIMENTO_MM {
+            return Err(ErroMapa::Corrompido);
+        }
+        Ok(mapa)
+    }
+}
+
+impl Default for MapaPista {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// "MAPA" em ASCII
+const MAGIA_MAPA: u32 = 0x4150_414D;
+const TAMANHO_CABECALHO: usize = 16;
+const TAMANHO_TRECHO: usize = 16;
+// Erro de arredondamento aceito entre o comprimento e a soma dos trechos
+const TOLERANCIA_COMPRIMENTO_MM: f32 = 1.0;
+
+// Tamanho máximo do mapa serializado
+pub const TAMANHO_MAPA_SERIALIZADO: usize = TAMANHO_CABECALHO + MAX_TRECHOS * TAMANHO_TRECHO;
+
+fn soma_verificacao(cabecalho: &[u8], trechos: &[u8]) -> u32 {
+    cabecalho
+        .iter()
+        .chain(trechos)
+        .fold(0x811C_9DC5u32, |h, &b| (h ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+// Monta o mapa durante a volta de aprendizado
+pub struct Aprendizado {
+    mapa: MapaPista,
+    distancia_mm: f32,
+    // Acumulado da amostra em andamento
+    passo_mm: f32,
+    giro_rad: f32,
+    correcao_max: f32,
+}
+
+impl Aprendizado {
+    pub const fn new() -> Self {
+        Self {
+            mapa: MapaPista::new(),
+            distancia_mm: 0.0,
+            passo_mm: 0.0,
+            giro_rad: 0.0,
+            correcao_max: 0.0,
+        }
+    }
+
+    pub fn reinicia(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn distancia_mm(&self) -> f32 {
+        self.distancia_mm
+    }
+
+    // Recebe o incremento da odometria e a correção de direção do mesmo período
+    pub fn registra(&mut self, deslocamento_mm: f32, giro_rad: f32, correcao: f32) -> Result<(), ErroMapa> {
+        self.passo_mm += deslocamento_mm.abs();
+        self.giro_rad += giro_rad;
+        self.correcao_max = self.correcao_max.max(correcao.abs());
+
+        if self.passo_mm < PASSO_AMOSTRA_MM {
+            return Ok(());
+        }
+
+        let curvatura = (self.giro_rad / self.passo_mm).abs();
+        let tipo = if curvatura > LIMIAR_CURVATURA || self.correcao_max > LIMIAR_CORRECAO {
+            TipoTrecho::Curva
+        } else {
+            TipoTrecho::Reta
+        };
+        let inicio = self.distancia_mm;
+        self.distancia_mm += self.passo_mm;
+        self.passo_mm = 0.0;
+        self.giro_rad = 0.0;
+        self.correcao_max = 0.0;
+
+        match self.mapa.trechos.last_mut() {
+            Some(atual) if atual.tipo == tipo => {
+                atual.fim_mm = self.distancia_mm;
+                atual.curvatura_max = atual.curvatura_max.max(curvatura);
+                Ok(())
+            }
+            _ => {
+                // Sem espaço, consolida os trechos curtos antes de desistir
+                if self.mapa.trechos.is_full() {
+                    self.mapa.consolida();
+                }
+                self.mapa
+                    .trechos
+                    .push(Trecho {
+                        tipo,
+                        inicio_mm: inicio,
+                        fim_mm: self.distancia_mm,
+                        curvatura_max: curvatura,
+                    })
+                    .map_err(|_| ErroMapa::Cheio)
+            }
+        }
+    }
+
+    // Fecha a volta e devolve o mapa consolidado
+    pub fn finaliza(&mut self) -> MapaPista {
+        self.mapa.comprimento_mm = self.distancia_mm;
+        self.mapa.consolida();
+        let mapa = core::mem::take(&mut self.mapa);
+        self.reinicia();
+        mapa
+    }
+}
+
+impl Default for Aprendizado {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct PerfilVelocidade {
+    pub velocidade_reta: f32,
+    pub velocidade_curva: f32,
+    // Desaceleração usada para frear antes das curvas, em mm/s²
+    pub desaceleracao: f32,
+}
+
+impl PerfilVelocidade {
+    pub const fn padrao() -> Self {
+        Self {
+            velocidade_reta: 1200.0,
+            velocidade_curva: 500.0,
+            desaceleracao: 2000.0,
+        }
+    }
+
+    // Velocidade base em um ponto da volta: devagar nas curvas e, nas retas,
+    // a maior velocidade que ainda permite frear até a próxima curva
+    pub fn velocidade_em(&self, mapa: &MapaPista, distancia_mm: f32) -> f32 {
+        let Some(indice) = mapa.indice_em(distancia_mm) else {
+            return self.velocidade_curva;
+        };
+        if mapa.trechos[indice].tipo == TipoTrecho::Curva {
+            return self.velocidade_curva;
+        }
+
+        match mapa.distancia_ate_curva(distancia_mm) {
+            Some(d) => {
+                let frenagem = (self.velocidade_curva * self.velocidade_curva + 2.0 * self.desaceleracao * d.max(0.0)).sqrt();
+                frenagem.min(self.velocidade_reta)
+            }
+            None => self.velocidade_reta,
+        }
+    }
+}
+
+impl Default for PerfilVelocidade {
+    fn default() -> Self {
+        Self::padrao()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapa_exemplo() -> MapaPista {
+        let mut aprendizado = Aprendizado::new();
+        // 400 mm de reta, 200 mm de curva e mais 300 mm de reta
+        for _ in 0..20 {
+            aprendizado.registra(20.0, 0.0, 0.0).unwrap();
+        }
+        for _ in 0..10 {
+            aprendizado.registra(20.0, 0.1, 0.0).unwrap();
+        }
+        for _ in 0..15 {
+            aprendizado.registra(20.0, 0.0, 0.0).unwrap();
+        }
+        aprendizado.finaliza()
+    }
+
+    #[test]
+    fn ida_e_volta() {
+        let mapa = mapa_exemplo();
+        assert_eq!(mapa.trechos().len(), 3);
+        let mut buf = [0u8; TAMANHO_MAPA_SERIALIZADO];
+        let tamanho = mapa.serializa(&mut buf).unwrap();
+        let lido = MapaPista::desserializa(&buf[..tamanho]).unwrap();
+        assert_eq!(lido.trechos(), mapa.trechos());
+        assert_eq!(lido.comprimento_mm(), 900.0);
+    }
+
+    #[test]
+    fn cabecalho_corrompido() {
+        let mut buf = [0u8; TAMANHO_MAPA_SERIALIZADO];
+        mapa_exemplo().serializa(&mut buf).unwrap();
+
+        // Comprimento alterado é pego pela soma de verificação
+        let mut alterado = buf;
+        alterado[8..12].copy_from_slice(&1000.0f32.to_le_bytes());
+        assert_eq!(MapaPista::desserializa(&alterado).err(), Some(ErroMapa::Corrompido));
+
+        // Quantidade menor, mesmo com a soma refeita, deixa um buraco no fim da volta
+        let mut alterado = buf;
+        alterado[4..8].copy_from_slice(&2u32.to_le_bytes());
+        let soma = soma_verificacao(&alterado[4..12], &alterado[TAMANHO_CABECALHO..TAMANHO_CABECALHO + 2 * TAMANHO_TRECHO]);
+        alterado[12..16].copy_from_slice(&soma.to_le_bytes());
+        assert_eq!(MapaPista::desserializa(&alterado).err(), Some(ErroMapa::Corrompido));
+
+        // Quantidade absurda não pode estourar o cálculo do tamanho
+        let mut alterado = buf;
+        alterado[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(MapaPista::desserializa(&alterado).err(), Some(ErroMapa::Corrompido));
+    }
+
+    #[test]
+    fn trecho_nao_finito() {
+        let mut buf = [0u8; TAMANHO_MAPA_SERIALIZADO];
+        let tamanho = mapa_exemplo().serializa(&mut buf).unwrap();
+
+        // Campos de cada trecho trocados por NaN ou infinito com a soma de verificação refeita
+        for (deslocamento, valor) in [(0, f32::NAN), (4, f32::INFINITY), (8, f32::NAN), (8, f32::NEG_INFINITY)] {
+            let mut alterado = buf;
+            let inicio = TAMANHO_CABECALHO + TAMANHO_TRECHO + deslocamento;
+            alterado[inicio..inicio + 4].copy_from_slice(&valor.to_le_bytes());
+            let soma = soma_verificacao(&alterado[4..12], &alterado[TAMANHO_CABECALHO..tamanho]);
+            alterado[12..16].copy_from_slice(&soma.to_le_bytes());
+            assert_eq!(MapaPista::desserializa(&alterado).err(), Some(ErroMapa::Corrompido));
+        }
+    }
+
+    // Mapa de exemplo: reta 0..400, curva 400..600 e reta 600..900. Com o perfil
+    // padrão a frenagem de 1200 para 500 mm/s leva (1200² - 500²) / (2 * 2000) = 297,5 mm
+    #[test]
+    fn perfil_na_reta_longe_da_curva() {
+        let mapa = mapa_exemplo();
+        let perfil = PerfilVelocidade::padrao();
+        assert_eq!(mapa.distancia_ate_curva(50.0), Some(350.0));
+        assert_eq!(perfil.velocidade_em(&mapa, 0.0), 1200.0);
+        assert_eq!(perfil.velocidade_em(&mapa, 100.0), 1200.0);
+    }
+
+    #[test]
+    fn perfil_freia_antes_da_curva() {
+        let mapa = mapa_exemplo();
+        let perfil = PerfilVelocidade::padrao();
+        // A 100 mm da curva: sqrt(500² + 2 * 2000 * 100)
+        let v = perfil.velocidade_em(&mapa, 300.0);
+        assert!((v - 650_000f32.sqrt()).abs() < 0.5, "{}", v);
+        // Cai sem parar até a velocidade da curva na entrada
+        let mut anterior = perfil.velocidade_reta;
+        for d in (110..400).step_by(10) {
+            let v = perfil.velocidade_em(&mapa, d as f32);
+            assert!(v <= anterior, "{} mm: {} > {}", d, v, anterior);
+            anterior = v;
+        }
+        assert!((anterior - perfil.velocidade_curva).abs() < 50.0);
+    }
+
+    #[test]
+    fn perfil_na_curva() {
+        let mapa = mapa_exemplo();
+        let perfil = PerfilVelocidade::padrao();
+        assert_eq!(mapa.indice_em(500.0), Some(1));
+        for d in [400.0, 500.0, 599.0] {
+            assert_eq!(perfil.velocidade_em(&mapa, d), 500.0);
+        }
+        // Sem mapa o perfil fica na velocidade segura
+        assert_eq!(perfil.velocidade_em(&MapaPista::new(), 100.0), 500.0);
+    }
+
+    #[test]
+    fn perfil_da_volta_na_pista() {
+        let mapa = mapa_exemplo();
+        // No último trecho a próxima curva é a da volta seguinte
+        assert_eq!(mapa.indice_em(800.0), Some(2));
+        assert_eq!(mapa.distancia_ate_curva(800.0), Some(500.0));
+        let perfil = PerfilVelocidade { desaceleracao: 500.0, ..PerfilVelocidade::padrao() };
+        // sqrt(500² + 2 * 500 * 500)
+        let v = perfil.velocidade_em(&mapa, 800.0);
+        assert!((v - 750_000f32.sqrt()).abs() < 0.5, "{}", v);
+    }
+
+    #[test]
+    fn perfil_alem_do_comprimento() {
+        let mapa = mapa_exemplo();
+        let perfil = PerfilVelocidade::padrao();
+        // A distância acumulada passa da volta: vale a posição dentro dela
+        assert_eq!(mapa.indice_em(900.0 + 500.0), Some(1));
+        assert_eq!(mapa.indice_em(2.0 * 900.0 + 50.0), Some(0));
+        assert_eq!(mapa.distancia_ate_curva(900.0 + 300.0), Some(100.0));
+        assert_eq!(perfil.velocidade_em(&mapa, 900.0 + 300.0), perfil.velocidade_em(&mapa, 300.0));
+        assert_eq!(perfil.velocidade_em(&mapa, 900.0 + 500.0), 500.0);
+    }
+}
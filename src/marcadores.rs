@@ -0,0 +1,230 @@
+// Marcadores laterais da pista lidos por dois sensores digitais auxiliares
+//
+// Direito: partida/chegada. Esquerdo: início/fim de curva. Os dois juntos são
+// um cruzamento de linhas e não contam como marcador.
+
+// Amostras seguidas (no ritmo do adc_task) para ativar ou liberar um sensor
+pub const AMOSTRAS_HISTERESE: u8 = 5;
+
+// Integrador com saturação: liga ao chegar no limite e só desliga ao voltar a
+// zero, filtrando ruído e reflexos curtos nas bordas do marcador
+pub struct Histerese {
+    contador: u8,
+    limite: u8,
+    ativo: bool,
+}
+
+impl Histerese {
+    pub const fn new(limite: u8) -> Self {
+        Self {
+            contador: 0,
+            limite,
+            ativo: false,
+        }
+    }
+
+    pub fn atualiza(&mut self, nivel: bool) -> bool {
+        if nivel {
+            self.contador = (self.contador + 1).min(self.limite);
+        } else {
+            self.contador = self.contador.saturating_sub(1);
+        }
+
+        if self.contador == self.limite {
+            self.ativo = true;
+        } else if self.contador == 0 {
+            self.ativo = false;
+        }
+        self.ativo
+    }
+
+    pub fn ativo(&self) -> bool {
+        self.ativo
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Marcador {
+    Partida,
+    Curva,
+    Cruzamento,
+}
+
+pub struct DetectorMarcadores {
+    esquerdo: Histerese,
+    direito: Histerese,
+    viu_esquerdo: bool,
+    viu_direito: bool,
+}
+
+impl DetectorMarcadores {
+    pub const fn new(amostras: u8) -> Self {
+        Self {
+            esquerdo: Histerese::new(amostras),
+            direito: Histerese::new(amostras),
+            viu_esquerdo: false,
+            viu_direito: false,
+        }
+    }
+
+    // O marcador é reportado quando os dois sensores saem dele, para que um
+    // cruzamento (que ativa os dois em instantes um pouco diferentes) não
+    // seja confundido com um marcador lateral
+    pub fn atualiza(&mut self, esquerdo: bool, direito: bool) -> Option<Marcador> {
+        let esquerdo = self.esquerdo.atualiza(esquerdo);
+        let direito = self.direito.atualiza(direito);
+        self.viu_esquerdo |= esquerdo;
+        self.viu_direito |= direito;
+
+        if esquerdo || direito {
+            return None;
+        }
+
+        let marcador = match (self.viu_esquerdo, self.viu_direito) {
+            (true, true) => Some(Marcador::Cruzamento),
+            (true, false) => Some(Marcador::Curva),
+            (false, true) => Some(Marcador::Partida),
+            (false, false) => None,
+        };
+        self.viu_esquerdo = false;
+        self.viu_direito = false;
+        marcador
+    }
+}
+
+impl Default for DetectorMarcadores {
+    fn default() -> Self {
+        Self::new(AMOSTRAS_HISTERESE)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum EventoVolta {
+    // Primeira passagem pela partida
+    Largada,
+    // Volta n completa
+    Volta(u32),
+    // Completou as voltas configuradas
+    Fim(u32),
+}
+
+pub struct ContadorVoltas {
+    // 0 roda sem limite
+    pub voltas_alvo: u32,
+    voltas: u32,
+    largou: bool,
+}
+
+impl ContadorVoltas {
+    pub const fn new(voltas_alvo: u32) -> Self {
+        Self {
+            voltas_alvo,
+            voltas: 0,
+            largou: false,
+        }
+    }
+
+    pub fn reinicia(&mut self) {
+        self.voltas = 0;
+        self.largou = false;
+    }
+
+    pub fn voltas(&self) -> u32 {
+        self.voltas
+    }
+
+    pub fn largou(&self) -> bool {
+        self.largou
+    }
+
+    pub fn passou_partida(&mut self) -> EventoVolta {
+        if !self.largou {
+            self.largou = true;
+            return EventoVolta::Largada;
+        }
+
+        self.voltas += 1;
+        if self.voltas_alvo != 0 && self.voltas >= self.voltas_alvo {
+            EventoVolta::Fim(self.voltas)
+        } else {
+            EventoVolta::Volta(self.voltas)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    // Mantém os dois sensores no mesmo nível por n amostras
+    fn passa(d: &mut DetectorMarcadores, esquerdo: bool, direito: bool, n: usize) -> Vec<Marcador> {
+        (0..n).filter_map(|_| d.atualiza(esquerdo, direito)).collect()
+    }
+
+    #[test]
+    fn histerese_no_limite() {
+        let mut h = Histerese::new(3);
+        assert!(!h.atualiza(true));
+        assert!(!h.atualiza(true));
+        assert!(h.atualiza(true));
+        // Só desliga quando o contador volta a zero
+        assert!(h.atualiza(false));
+        assert!(h.atualiza(false));
+        assert!(!h.atualiza(false));
+
+        // Ruído alternado nunca chega no limite
+        for i in 0..20 {
+            assert!(!h.atualiza(i % 2 == 0));
+        }
+    }
+
+    #[test]
+    fn marcador_lateral() {
+        let mut d = DetectorMarcadores::new(3);
+        assert!(passa(&mut d, true, false, 5).is_empty());
+        assert_eq!(passa(&mut d, false, false, 5), [Marcador::Curva]);
+        assert!(passa(&mut d, false, true, 5).is_empty());
+        assert_eq!(passa(&mut d, false, false, 5), [Marcador::Partida]);
+    }
+
+    #[test]
+    fn reflexo_curto_nao_e_marcador() {
+        let mut d = DetectorMarcadores::new(3);
+        assert!(passa(&mut d, false, true, 2).is_empty());
+        assert!(passa(&mut d, false, false, 10).is_empty());
+    }
+
+    #[test]
+    fn cruzamento_com_sensores_defasados() {
+        let mut d = DetectorMarcadores::new(3);
+        // O esquerdo entra antes e sai antes do direito
+        assert!(passa(&mut d, true, false, 3).is_empty());
+        assert!(passa(&mut d, true, true, 5).is_empty());
+        assert!(passa(&mut d, false, true, 2).is_empty());
+        assert_eq!(passa(&mut d, false, false, 5), [Marcador::Cruzamento]);
+    }
+
+    #[test]
+    fn voltas_ate_o_fim() {
+        let mut d = DetectorMarcadores::new(AMOSTRAS_HISTERESE);
+        let mut voltas = ContadorVoltas::new(2);
+        let limite = AMOSTRAS_HISTERESE as usize;
+        let mut eventos = Vec::new();
+        for _ in 0..3 {
+            // Uma amostra a menos que o limite não ativa; a que completa o limite sim
+            assert!(passa(&mut d, false, true, limite - 1).is_empty());
+            assert!(passa(&mut d, false, true, 1).is_empty());
+            for marcador in passa(&mut d, false, false, limite) {
+                assert_eq!(marcador, Marcador::Partida);
+                eventos.push(voltas.passou_partida());
+            }
+        }
+        assert_eq!(eventos, [EventoVolta::Largada, EventoVolta::Volta(1), EventoVolta::Fim(2)]);
+        assert_eq!(voltas.voltas(), 2);
+
+        voltas.reinicia();
+        assert!(!voltas.largou());
+        assert_eq!(voltas.passou_partida(), EventoVolta::Largada);
+    }
+}
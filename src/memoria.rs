@@ -0,0 +1,56 @@
+// Medida de uso da pilha por pintura
+//
+// Na partida a região livre entre o fim do .bss e a pilha é preenchida com um
+// padrão. A pilha cresce para baixo, então as palavras do início da região que
+// ainda têm o padrão nunca foram usadas: o resto é o pico de uso.
+
+pub const PADRAO_PILHA: u32 = 0xA5A5_A5A5;
+
+/// Preenche [inicio, fim) com o padrão
+///
+/// # Safety
+/// A região precisa ser RAM válida, alinhada a 4 bytes e sem nada em uso,
+/// ou seja, abaixo do ponteiro de pilha atual.
+pub unsafe fn pinta(inicio: *mut u32, fim: *mut u32) {
+    let mut p = inicio;
+    while p < fim {
+        p.write_volatile(PADRAO_PILHA);
+        p = p.add(1);
+    }
+}
+
+/// Bytes a partir de inicio que continuam com o padrão
+///
+/// # Safety
+/// A região precisa ser RAM válida e alinhada a 4 bytes, como em pinta.
+pub unsafe fn intocado(inicio: *const u32, fim: *const u32) -> usize {
+    let mut p = inicio;
+    while p < fim && p.read_volatile() == PADRAO_PILHA {
+        p = p.add(1);
+    }
+    p as usize - inicio as usize
+}
+
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct UsoPilha {
+    // Do fim do .bss ao topo da RAM
+    pub total: usize,
+    pub pico: usize,
+    pub atual: usize,
+}
+
+impl UsoPilha {
+    // Endereços: inicio (fim do .bss), topo (pilha inicial), ponteiro de pilha atual e bytes intocados
+    pub fn calcula(inicio: usize, topo: usize, sp: usize, intocado: usize) -> Self {
+        let total = topo - inicio;
+        Self {
+            total,
+            pico: total - intocado.min(total),
+            atual: topo.saturating_sub(sp),
+        }
+    }
+
+    pub fn livre(&self) -> usize {
+        self.total - self.pico
+    }
+}
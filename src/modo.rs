@@ -0,0 +1,265 @@
+// Modo de operação do robô
+//
+// Só a tabela de transições fica aqui; as ações de entrada e saída de cada
+// modo (motores, calibração, LEDs) ficam com quem conhece o hardware.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Modo {
+    // Parado esperando comando, motores desligados
+    Ocioso,
+    Calibrando,
+    // Pronto para largar no próximo aperto do botão
+    Armado,
+    Seguindo,
+    // Parou depois de correr (fim das voltas, botão ou console)
+    Parado,
+    Falha,
+    // Teste de bancada: os comandos motor e vel só valem aqui
+    Bancada,
+}
+
+pub const MODOS: [Modo; 7] = [
+    Modo::Ocioso,
+    Modo::Calibrando,
+    Modo::Armado,
+    Modo::Seguindo,
+    Modo::Parado,
+    Modo::Falha,
+    Modo::Bancada,
+];
+
+// Nomes aceitos pelo comando mode, na ordem de MODOS
+pub const NOMES_MODOS: [&str; 7] = ["ocioso", "calibrando", "armado", "seguindo", "parado", "falha", "bancada"];
+
+impl Modo {
+    pub fn nome(&self) -> &'static str {
+        NOMES_MODOS[*self as usize]
+    }
+
+    pub fn de_nome(nome: &str) -> Option<Modo> {
+        NOMES_MODOS.iter().position(|n| *n == nome).map(|i| MODOS[i])
+    }
+
+    // Falha é alcançável de qualquer modo, mas só por falha(), nunca a pedido
+    pub fn pode_ir(&self, destino: Modo) -> bool {
+        use Modo::*;
+        matches!(
+            (*self, destino),
+            (Ocioso, Calibrando | Armado | Seguindo | Bancada)
+                | (Calibrando, Ocioso | Parado)
+                | (Armado, Ocioso | Calibrando | Seguindo | Parado)
+                | (Seguindo, Parado)
+                | (Parado, Ocioso | Calibrando | Armado | Seguindo | Bancada)
+                | (Falha, Ocioso)
+                | (Bancada, Ocioso | Parado)
+        )
+    }
+
+    // Sequência do clique do botão: arma, larga, para e reconhece falha; da
+    // bancada volta para ocioso
+    pub fn seguinte(&self) -> Option<Modo> {
+        match self {
+            Modo::Ocioso | Modo::Parado => Some(Modo::Armado),
+            Modo::Armado => Some(Modo::Seguindo),
+            Modo::Seguindo => Some(Modo::Parado),
+            Modo::Falha | Modo::Bancada => Some(Modo::Ocioso),
+            Modo::Calibrando => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum CodigoFalha {
+    // A calibração terminou sem ver a linha em algum sensor
+    CalibracaoInvalida,
+    // Linha perdida por tempo demais seguindo
+    LinhaPerdida,
+}
+
+impl CodigoFalha {
+    // Número de piscadas do código no LED de estado
+    pub fn piscadas(&self) -> u8 {
+        match self {
+            CodigoFalha::CalibracaoInvalida => 2,
+            CodigoFalha::LinhaPerdida => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub struct TransicaoInvalida {
+    pub de: Modo,
+    pub para: Modo,
+}
+
+pub struct MaquinaModo {
+    modo: Modo,
+    desde_ms: u64,
+    falha: Option<CodigoFalha>,
+    transicoes: u32,
+}
+
+impl MaquinaModo {
+    pub const fn new() -> Self {
+        Self {
+            modo: Modo::Ocioso,
+            desde_ms: 0,
+            falha: None,
+            transicoes: 0,
+        }
+    }
+
+    pub fn modo(&self) -> Modo {
+        self.modo
+    }
+
+    pub fn desde_ms(&self) -> u64 {
+        self.desde_ms
+    }
+
+    // Última falha, guardada até a próxima
+    pub fn falha(&self) -> Option<CodigoFalha> {
+        self.falha
+    }
+
+    pub fn transicoes(&self) -> u32 {
+        self.transicoes
+    }
+
+    // Devolve o modo anterior para quem chama fazer as ações de saída e entrada;
+    // pedir o modo atual não é erro nem conta como transição
+    pub fn pede(&mut self, destino: Modo, agora_ms: u64) -> Result<Modo, TransicaoInvalida> {
+        if destino == self.modo {
+            return Ok(self.modo);
+        }
+        if !self.modo.pode_ir(destino) {
+            return Err(TransicaoInvalida { de: self.modo, para: destino });
+        }
+        Ok(self.entra(destino, agora_ms))
+    }
+
+    // Uma falha já ativa não é sobrescrita, para não perder a causa original
+    pub fn registra_falha(&mut self, codigo: CodigoFalha, agora_ms: u64) -> Option<Modo> {
+        if self.modo == Modo::Falha {
+            return None;
+        }
+        self.falha = Some(codigo);
+        Some(self.entra(Modo::Falha, agora_ms))
+    }
+
+    fn entra(&mut self, destino: Modo, agora_ms: u64) -> Modo {
+        self.transicoes = self.transicoes.wrapping_add(1);
+        self.desde_ms = agora_ms;
+        core::mem::replace(&mut self.modo, destino)
+    }
+}
+
+impl Default for MaquinaModo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bancada_so_com_o_robo_parado() {
+        let mut m = MaquinaModo::new();
+        assert_eq!(m.pede(Modo::Bancada, 1), Ok(Modo::Ocioso));
+        assert!(m.pede(Modo::Seguindo, 2).is_err());
+        assert!(m.pede(Modo::Armado, 2).is_err());
+        assert_eq!(Modo::Bancada.seguinte(), Some(Modo::Ocioso));
+        assert_eq!(m.pede(Modo::Parado, 3), Ok(Modo::Bancada));
+        assert_eq!(m.pede(Modo::Bancada, 4), Ok(Modo::Parado));
+
+        for modo in [Modo::Calibrando, Modo::Armado, Modo::Seguindo, Modo::Falha] {
+            assert!(!modo.pode_ir(Modo::Bancada));
+        }
+        assert_eq!(Modo::de_nome("bancada"), Some(Modo::Bancada));
+    }
+
+    #[test]
+    fn tabela_de_transicoes() {
+        use Modo::*;
+        let permitidas: [(Modo, &[Modo]); 7] = [
+            (Ocioso, &[Calibrando, Armado, Seguindo, Bancada]),
+            (Calibrando, &[Ocioso, Parado]),
+            (Armado, &[Ocioso, Calibrando, Seguindo, Parado]),
+            (Seguindo, &[Parado]),
+            (Parado, &[Ocioso, Calibrando, Armado, Seguindo, Bancada]),
+            (Falha, &[Ocioso]),
+            (Bancada, &[Ocioso, Parado]),
+        ];
+        for (de, destinos) in permitidas {
+            for para in MODOS {
+                assert_eq!(de.pode_ir(para), destinos.contains(&para), "{:?} -> {:?}", de, para);
+            }
+            // Falha nunca é pedida, nem o modo atual como transição
+            assert!(!de.pode_ir(Falha));
+            assert!(!de.pode_ir(de));
+        }
+    }
+
+    #[test]
+    fn sequencia_do_clique() {
+        use Modo::*;
+        let seguintes = [
+            (Ocioso, Some(Armado)),
+            (Calibrando, None),
+            (Armado, Some(Seguindo)),
+            (Seguindo, Some(Parado)),
+            (Parado, Some(Armado)),
+            (Falha, Some(Ocioso)),
+            (Bancada, Some(Ocioso)),
+        ];
+        for (de, seguinte) in seguintes {
+            assert_eq!(de.seguinte(), seguinte, "{:?}", de);
+            // O clique sempre pede uma transição válida
+            if let Some(para) = seguinte {
+                assert!(de.pode_ir(para), "{:?} -> {:?}", de, para);
+            }
+        }
+        for (i, modo) in MODOS.iter().enumerate() {
+            assert_eq!(Modo::de_nome(modo.nome()), Some(*modo));
+            assert_eq!(*modo as usize, i);
+        }
+    }
+
+    #[test]
+    fn modo_atual_e_pedido_recusado() {
+        let mut m = MaquinaModo::new();
+        assert_eq!(m.pede(Modo::Seguindo, 10), Ok(Modo::Ocioso));
+        assert_eq!((m.modo(), m.desde_ms(), m.transicoes()), (Modo::Seguindo, 10, 1));
+
+        // Pedir o modo atual devolve ele mesmo sem contar nem mudar o instante
+        assert_eq!(m.pede(Modo::Seguindo, 20), Ok(Modo::Seguindo));
+        assert_eq!((m.desde_ms(), m.transicoes()), (10, 1));
+
+        // Transição recusada não muda nada
+        assert_eq!(
+            m.pede(Modo::Armado, 30),
+            Err(TransicaoInvalida { de: Modo::Seguindo, para: Modo::Armado })
+        );
+        assert_eq!((m.modo(), m.desde_ms(), m.transicoes()), (Modo::Seguindo, 10, 1));
+    }
+
+    #[test]
+    fn falha_ativa_nao_e_sobrescrita() {
+        let mut m = MaquinaModo::new();
+        m.pede(Modo::Seguindo, 1).unwrap();
+        assert_eq!(m.registra_falha(CodigoFalha::LinhaPerdida, 5), Some(Modo::Seguindo));
+        assert_eq!((m.modo(), m.falha(), m.desde_ms()), (Modo::Falha, Some(CodigoFalha::LinhaPerdida), 5));
+
+        assert_eq!(m.registra_falha(CodigoFalha::CalibracaoInvalida, 9), None);
+        assert_eq!((m.falha(), m.desde_ms(), m.transicoes()), (Some(CodigoFalha::LinhaPerdida), 5, 2));
+
+        // Só sai de Falha para ocioso; o código fica guardado até a próxima falha
+        assert!(m.pede(Modo::Seguindo, 10).is_err());
+        assert_eq!(m.pede(Modo::Ocioso, 11), Ok(Modo::Falha));
+        assert_eq!(m.falha(), Some(CodigoFalha::LinhaPerdida));
+        assert_eq!(m.registra_falha(CodigoFalha::CalibracaoInvalida, 12), Some(Modo::Ocioso));
+        assert_eq!(m.falha(), Some(CodigoFalha::CalibracaoInvalida));
+    }
+}
@@ -0,0 +1,31 @@
+// Tipos e limites dos motores, sem dependência do hardware; o driver da
+// ponte H fica em ponte.rs, só no alvo
+
+// Duty com sinal aceito pelo driver: -DUTY_MAX (ré) ..= DUTY_MAX (frente)
+pub const DUTY_MAX: i16 = 1000;
+
+pub const FREQUENCIA_PADRAO_HZ: u32 = 20_000;
+// Faixa aceita para o PWM: acima do clock do timer a conta do prescaler entra em pânico
+pub const FREQUENCIA_MIN_HZ: u32 = 100;
+pub const FREQUENCIA_MAX_HZ: u32 = 100_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Lado {
+    Esquerdo,
+    Direito,
+}
+
+// O que fazer com o motor quando o duty é zero
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Parada {
+    Freio,
+    Livre,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum ComandoMotores {
+    Duty { esquerdo: i16, direito: i16 },
+    Motor(Lado, i16),
+    Para(Parada),
+    Frequencia(u32),
+}
@@ -0,0 +1,163 @@
+// Odometria do robô diferencial: integra os deslocamentos das rodas em (x, y, θ)
+
+use core::f32::consts::PI;
+// Nos testes do host a std já traz as funções de f32 e o import fica sem uso
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::encoder::GeometriaRoda;
+
+// Distância entre os centros das rodas
+pub const BITOLA_MM: f32 = 120.0;
+
+// x e y em mm a partir do ponto de partida, θ em radianos em (-π, π], 0 olhando para +x
+#[derive(Clone, Copy, Default, PartialEq, Debug, defmt::Format)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub theta: f32,
+}
+
+pub struct Odometria {
+    pub geometria: GeometriaRoda,
+    pub bitola_mm: f32,
+    pose: Pose,
+    distancia_mm: f32,
+}
+
+impl Odometria {
+    pub const fn new(geometria: GeometriaRoda, bitola_mm: f32) -> Self {
+        Self {
+            geometria,
+            bitola_mm,
+            pose: Pose { x: 0.0, y: 0.0, theta: 0.0 },
+            distancia_mm: 0.0,
+        }
+    }
+
+    pub fn zera(&mut self) {
+        self.pose = Pose::default();
+        self.distancia_mm = 0.0;
+    }
+
+    // Deslocamento do centro (mm) e giro (rad) correspondentes à variação de ticks
+    pub fn incremento(&self, delta_esquerdo: i32, delta_direito: i32) -> (f32, f32) {
+        let mm_por_tick = self.geometria.mm_por_tick();
+        let esquerdo = delta_esquerdo as f32 * mm_por_tick;
+        let direito = delta_direito as f32 * mm_por_tick;
+
+        ((esquerdo + direito) / 2.0, (direito - esquerdo) / self.bitola_mm)
+    }
+
+    // Integra a variação de ticks de cada roda desde a última chamada
+    pub fn atualiza(&mut self, delta_esquerdo: i32, delta_direito: i32) -> Pose {
+        let (deslocamento, giro) = self.incremento(delta_esquerdo, delta_direito);
+
+        // Integração pelo ponto médio do arco
+        let direcao = self.pose.theta + giro / 2.0;
+        self.pose.x += deslocamento * direcao.cos();
+        self.pose.y += deslocamento * direcao.sin();
+        self.pose.theta = normaliza_angulo(self.pose.theta + giro);
+        self.distancia_mm += deslocamento.abs();
+
+        self.pose
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    // Distância percorrida pelo centro do robô, sempre crescente
+    pub fn distancia_mm(&self) -> f32 {
+        self.distancia_mm
+    }
+}
+
+impl Default for Odometria {
+    fn default() -> Self {
+        Self::new(GeometriaRoda::default(), BITOLA_MM)
+    }
+}
+
+// Leva o ângulo para (-π, π]
+pub fn normaliza_angulo(angulo: f32) -> f32 {
+    let mut angulo = angulo;
+    while angulo > PI {
+        angulo -= 2.0 * PI;
+    }
+    while angulo <= -PI {
+        angulo += 2.0 * PI;
+    }
+    angulo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 mm por tick e 100 mm de bitola deixam as contas redondas
+    fn odometria() -> Odometria {
+        Odometria::new(GeometriaRoda::new(1000, 1000.0 / PI), 100.0)
+    }
+
+    fn perto(a: f32, b: f32, tolerancia: f32) -> bool {
+        (a - b).abs() <= tolerancia
+    }
+
+    #[test]
+    fn linha_reta() {
+        let mut odo = odometria();
+        for _ in 0..100 {
+            odo.atualiza(10, 10);
+        }
+        let pose = odo.pose();
+        assert!(perto(pose.x, 1000.0, 0.5), "{:?}", pose);
+        assert!(perto(pose.y, 0.0, 0.01) && pose.theta == 0.0, "{:?}", pose);
+        assert!(perto(odo.distancia_mm(), 1000.0, 0.5));
+    }
+
+    #[test]
+    fn giro_no_lugar() {
+        let mut odo = odometria();
+        // 0,02 rad por passo, 75 passos: pouco menos de 90 graus à esquerda
+        for _ in 0..75 {
+            odo.atualiza(-1, 1);
+        }
+        let pose = odo.pose();
+        assert!(perto(pose.x, 0.0, 0.01) && perto(pose.y, 0.0, 0.01), "{:?}", pose);
+        assert!(perto(pose.theta, 1.5, 1e-3), "{:?}", pose);
+        assert_eq!(odo.distancia_mm(), 0.0);
+
+        // Girando mais uma volta e meia o ângulo continua em (-π, π]
+        for _ in 0..471 {
+            odo.atualiza(-1, 1);
+        }
+        let theta = odo.pose().theta;
+        assert!(theta > -PI && theta <= PI && perto(theta, 1.5 + 9.42 - 4.0 * PI, 1e-2), "{}", theta);
+    }
+
+    #[test]
+    fn arco_de_raio_constante() {
+        let mut odo = odometria();
+        // Raio de 500 mm: rodas a 450 e 550 mm do centro, 0,02 rad e 10 mm por passo
+        let passos = 150;
+        for _ in 0..passos {
+            odo.atualiza(9, 11);
+        }
+        let angulo = passos as f32 * 0.02;
+        let pose = odo.pose();
+        assert!(perto(pose.theta, angulo, 1e-2), "{:?}", pose);
+        assert!(perto(pose.x, 500.0 * angulo.sin(), 3.0), "{:?}", pose);
+        assert!(perto(pose.y, 500.0 * (1.0 - angulo.cos()), 3.0), "{:?}", pose);
+        assert!(perto(odo.distancia_mm(), 1500.0, 0.5));
+    }
+
+    #[test]
+    fn zera() {
+        let mut odo = odometria();
+        odo.atualiza(50, 80);
+        odo.zera();
+        assert_eq!(odo.pose(), Pose::default());
+        assert_eq!(odo.distancia_mm(), 0.0);
+    }
+}
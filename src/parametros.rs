@@ -0,0 +1,296 @@
+// Tabela de parâmetros ajustáveis pelo console (get/set/params)
+//
+// As definições são constantes; a tabela guarda só os valores atuais e
+// valida tipo e limites antes de aceitar uma alteração.
+
+use core::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub enum Valor {
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+}
+
+impl Valor {
+    // Lê o texto como um valor do mesmo tipo de self
+    pub fn interpreta(&self, texto: &str) -> Option<Valor> {
+        let texto = texto.trim();
+        match self {
+            Valor::U32(_) => texto.parse().ok().map(Valor::U32),
+            Valor::I32(_) => texto.parse().ok().map(Valor::I32),
+            Valor::F32(_) => texto.parse().ok().map(Valor::F32),
+            Valor::Bool(_) => match texto {
+                "1" | "true" | "on" => Some(Valor::Bool(true)),
+                "0" | "false" | "off" => Some(Valor::Bool(false)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn mesmo_tipo(&self, outro: &Valor) -> bool {
+        core::mem::discriminant(self) == core::mem::discriminant(outro)
+    }
+
+    pub fn como_u32(&self) -> u32 {
+        match *self {
+            Valor::U32(v) => v,
+            Valor::I32(v) => v.max(0) as u32,
+            Valor::F32(v) => v as u32,
+            Valor::Bool(v) => v as u32,
+        }
+    }
+
+    pub fn como_i32(&self) -> i32 {
+        match *self {
+            Valor::U32(v) => v.min(i32::MAX as u32) as i32,
+            Valor::I32(v) => v,
+            Valor::F32(v) => v as i32,
+            Valor::Bool(v) => v as i32,
+        }
+    }
+
+    pub fn como_f32(&self) -> f32 {
+        match *self {
+            Valor::U32(v) => v as f32,
+            Valor::I32(v) => v as f32,
+            Valor::F32(v) => v,
+            Valor::Bool(v) => v as u32 as f32,
+        }
+    }
+
+    pub fn como_bool(&self) -> bool {
+        match *self {
+            Valor::Bool(v) => v,
+            _ => self.como_i32() != 0,
+        }
+    }
+
+    fn menor_que(&self, outro: &Valor) -> bool {
+        match (self, outro) {
+            (Valor::U32(a), Valor::U32(b)) => a < b,
+            (Valor::I32(a), Valor::I32(b)) => a < b,
+            (Valor::F32(a), Valor::F32(b)) => a < b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Valor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Valor::U32(v) => write!(f, "{}", v),
+            Valor::I32(v) => write!(f, "{}", v),
+            Valor::F32(v) => write!(f, "{}", v),
+            Valor::Bool(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+pub struct Parametro {
+    pub nome: &'static str,
+    pub padrao: Valor,
+    // Ignorados para bool
+    pub min: Valor,
+    pub max: Valor,
+    pub unidade: &'static str,
+    pub descricao: &'static str,
+}
+
+impl Parametro {
+    pub const fn u32(nome: &'static str, padrao: u32, min: u32, max: u32, unidade: &'static str, descricao: &'static str) -> Self {
+        Self { nome, padrao: Valor::U32(padrao), min: Valor::U32(min), max: Valor::U32(max), unidade, descricao }
+    }
+
+    pub const fn i32(nome: &'static str, padrao: i32, min: i32, max: i32, unidade: &'static str, descricao: &'static str) -> Self {
+        Self { nome, padrao: Valor::I32(padrao), min: Valor::I32(min), max: Valor::I32(max), unidade, descricao }
+    }
+
+    pub const fn f32(nome: &'static str, padrao: f32, min: f32, max: f32, unidade: &'static str, descricao: &'static str) -> Self {
+        Self { nome, padrao: Valor::F32(padrao), min: Valor::F32(min), max: Valor::F32(max), unidade, descricao }
+    }
+
+    pub const fn bool(nome: &'static str, padrao: bool, descricao: &'static str) -> Self {
+        Self { nome, padrao: Valor::Bool(padrao), min: Valor::Bool(false), max: Valor::Bool(true), unidade: "", descricao }
+    }
+
+    pub fn aceita(&self, valor: &Valor) -> Result<(), ErroParametro> {
+        if !valor.mesmo_tipo(&self.padrao) {
+            return Err(ErroParametro::TipoErrado);
+        }
+        // NaN também fica de fora: não é maior nem menor que os limites
+        if let Valor::F32(v) = valor {
+            if v.is_nan() {
+                return Err(ErroParametro::ForaDosLimites);
+            }
+        }
+        if valor.menor_que(&self.min) || self.max.menor_que(valor) {
+            return Err(ErroParametro::ForaDosLimites);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum ErroParametro {
+    Desconhecido,
+    TipoErrado,
+    ValorInvalido,
+    ForaDosLimites,
+}
+
+// Nomes das definições, para o TAB completar get/set
+pub const fn nomes_parametros<const N: usize>(definicoes: &[Parametro; N]) -> [&'static str; N] {
+    let mut nomes = [""; N];
+    let mut i = 0;
+    while i < N {
+        nomes[i] = definicoes[i].nome;
+        i += 1;
+    }
+    nomes
+}
+
+// Comparação de nomes usável em const, para conferir a ordem das definições
+pub const fn mesmo_nome(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+pub struct TabelaParametros<const N: usize> {
+    definicoes: &'static [Parametro; N],
+    valores: [Valor; N],
+}
+
+impl<const N: usize> TabelaParametros<N> {
+    pub const fn new(definicoes: &'static [Parametro; N]) -> Self {
+        let mut valores = [Valor::Bool(false); N];
+        let mut i = 0;
+        while i < N {
+            valores[i] = definicoes[i].padrao;
+            i += 1;
+        }
+        Self { definicoes, valores }
+    }
+
+    pub fn definicoes(&self) -> &'static [Parametro; N] {
+        self.definicoes
+    }
+
+    pub fn indice(&self, nome: &str) -> Option<usize> {
+        self.definicoes.iter().position(|p| p.nome == nome)
+    }
+
+    pub fn valor(&self, indice: usize) -> Valor {
+        self.valores[indice]
+    }
+
+    pub fn define(&mut self, indice: usize, valor: Valor) -> Result<(), ErroParametro> {
+        let definicao = self.definicoes.get(indice).ok_or(ErroParametro::Desconhecido)?;
+        definicao.aceita(&valor)?;
+        self.valores[indice] = valor;
+        Ok(())
+    }
+
+    // Interpreta o texto no tipo do parâmetro; devolve o índice alterado
+    pub fn define_texto(&mut self, nome: &str, texto: &str) -> Result<usize, ErroParametro> {
+        let indice = self.indice(nome).ok_or(ErroParametro::Desconhecido)?;
+        let valor = self.valores[indice].interpreta(texto).ok_or(ErroParametro::ValorInvalido)?;
+        self.define(indice, valor)?;
+        Ok(indice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DEFINICOES: [Parametro; 4] = [
+        Parametro::u32("periodo_ms", 10, 1, 100, "ms", "período"),
+        Parametro::i32("offset", 0, -50, 50, "", "deslocamento"),
+        Parametro::f32("kp", 1.0, 0.0, 10.0, "", "ganho"),
+        Parametro::bool("debug", false, "saída de depuração"),
+    ];
+
+    // Conferido na compilação, como no binário
+    const _: () = assert!(mesmo_nome(nomes_parametros(&DEFINICOES)[2], "kp"));
+
+    #[test]
+    fn tipo_errado() {
+        let p = &DEFINICOES[0];
+        assert_eq!(p.aceita(&Valor::I32(5)), Err(ErroParametro::TipoErrado));
+        assert_eq!(p.aceita(&Valor::F32(5.0)), Err(ErroParametro::TipoErrado));
+        assert_eq!(DEFINICOES[3].aceita(&Valor::U32(1)), Err(ErroParametro::TipoErrado));
+
+        let mut t = TabelaParametros::new(&DEFINICOES);
+        assert_eq!(t.define(0, Valor::Bool(true)), Err(ErroParametro::TipoErrado));
+        assert_eq!(t.define(9, Valor::U32(1)), Err(ErroParametro::Desconhecido));
+        assert_eq!(t.valor(0), Valor::U32(10));
+    }
+
+    #[test]
+    fn limites_inclusivos() {
+        let mut t = TabelaParametros::new(&DEFINICOES);
+        assert_eq!(t.define_texto("periodo_ms", "1"), Ok(0));
+        assert_eq!(t.define_texto("periodo_ms", "100"), Ok(0));
+        assert_eq!(t.define_texto("periodo_ms", "101"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.define_texto("periodo_ms", "0"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.valor(0), Valor::U32(100));
+
+        assert_eq!(t.define_texto("offset", " -50 "), Ok(1));
+        assert_eq!(t.define_texto("offset", "-51"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.define_texto("kp", "10.5"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.valor(1), Valor::I32(-50));
+    }
+
+    #[test]
+    fn nao_finitos() {
+        let mut t = TabelaParametros::new(&DEFINICOES);
+        assert_eq!(t.define_texto("kp", "NaN"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.define_texto("kp", "inf"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.define_texto("kp", "-inf"), Err(ErroParametro::ForaDosLimites));
+        assert_eq!(t.valor(2), Valor::F32(1.0));
+    }
+
+    #[test]
+    fn texto_invalido_e_nome_desconhecido() {
+        let mut t = TabelaParametros::new(&DEFINICOES);
+        assert_eq!(t.define_texto("periodo_ms", "-1"), Err(ErroParametro::ValorInvalido));
+        assert_eq!(t.define_texto("offset", "1.5"), Err(ErroParametro::ValorInvalido));
+        assert_eq!(t.define_texto("kp", "rapido"), Err(ErroParametro::ValorInvalido));
+        assert_eq!(t.define_texto("ki", "1"), Err(ErroParametro::Desconhecido));
+        assert_eq!(t.indice("debug"), Some(3));
+        assert_eq!(t.indice("Debug"), None);
+    }
+
+    #[test]
+    fn bool() {
+        let mut t = TabelaParametros::new(&DEFINICOES);
+        for (texto, esperado) in [("1", true), ("off", false), ("on", true), ("false", false), ("true", true)] {
+            assert_eq!(t.define_texto("debug", texto), Ok(3));
+            assert_eq!(t.valor(3), Valor::Bool(esperado));
+        }
+        assert_eq!(t.define_texto("debug", "sim"), Err(ErroParametro::ValorInvalido));
+        assert_eq!(t.define_texto("debug", "2"), Err(ErroParametro::ValorInvalido));
+        assert_eq!(t.valor(3), Valor::Bool(true));
+    }
+
+    #[test]
+    fn nomes() {
+        assert_eq!(nomes_parametros(&DEFINICOES), ["periodo_ms", "offset", "kp", "debug"]);
+        assert!(mesmo_nome("kp", "kp"));
+        assert!(!mesmo_nome("kp", "kd"));
+        assert!(!mesmo_nome("kp", "kp2"));
+        assert!(mesmo_nome("", ""));
+    }
+}
@@ -0,0 +1,194 @@
+// Controlador PID do seguidor de linha
+
+use crate::sensores::POSICAO_CENTRO;
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct GanhosPid {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+impl GanhosPid {
+    pub const fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self { kp, ki, kd }
+    }
+
+    // NaN ou infinito em qualquer ganho envenena a saída (e o clamp do integral entra em pânico)
+    pub fn finitos(&self) -> bool {
+        self.kp.is_finite() && self.ki.is_finite() && self.kd.is_finite()
+    }
+
+    const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+}
+
+pub struct Pid {
+    ganhos: GanhosPid,
+    // Saída saturada em +-limite_saida
+    pub limite_saida: f32,
+    // Contribuição máxima do termo integral (anti-windup)
+    pub limite_integral: f32,
+    // Filtro passa-baixa de primeira ordem na derivada: 0 sem filtro, perto de 1 filtra mais
+    pub alfa_derivada: f32,
+    integral: f32,
+    erro_anterior: f32,
+    derivada: f32,
+    primeira_amostra: bool,
+}
+
+impl Pid {
+    // Com ganhos inválidos começa zerado, até um define_ganhos aceito
+    pub fn new(ganhos: GanhosPid, limite_saida: f32, limite_integral: f32, alfa_derivada: f32) -> Self {
+        Self {
+            ganhos: if ganhos.finitos() { ganhos } else { GanhosPid::ZERO },
+            limite_saida,
+            limite_integral,
+            alfa_derivada,
+            integral: 0.0,
+            erro_anterior: 0.0,
+            derivada: 0.0,
+            primeira_amostra: true,
+        }
+    }
+
+    pub fn ganhos(&self) -> GanhosPid {
+        self.ganhos
+    }
+
+    // Recusa ganhos que não são finitos, mantendo os anteriores
+    pub fn define_ganhos(&mut self, ganhos: GanhosPid) -> bool {
+        if !ganhos.finitos() {
+            return false;
+        }
+        self.ganhos = ganhos;
+        true
+    }
+
+    // Descarta o histórico, usado ao (re)começar a seguir a linha
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.erro_anterior = 0.0;
+        self.derivada = 0.0;
+        self.primeira_amostra = true;
+    }
+
+    // Calcula a correção para um erro amostrado a cada `dt` segundos
+    pub fn atualiza(&mut self, erro: f32, dt: f32) -> f32 {
+        let GanhosPid { kp, ki, kd } = self.ganhos;
+
+        // Na primeira amostra não há erro anterior para derivar
+        if self.primeira_amostra {
+            self.erro_anterior = erro;
+            self.primeira_amostra = false;
+        }
+
+        if ki != 0.0 {
+            let limite = self.limite_integral / ki.abs();
+            self.integral = (self.integral + erro * dt).clamp(-limite, limite);
+        } else {
+            self.integral = 0.0;
+        }
+
+        let bruta = if dt > 0.0 { (erro - self.erro_anterior) / dt } else { 0.0 };
+        self.derivada = self.alfa_derivada * self.derivada + (1.0 - self.alfa_derivada) * bruta;
+        self.erro_anterior = erro;
+
+        let saida = kp * erro + ki * self.integral + kd * self.derivada;
+        saida.clamp(-self.limite_saida, self.limite_saida)
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+}
+
+// Erro da posição 0..7000 em relação ao centro do array; positivo com a linha à direita
+pub fn erro_posicao(posicao: u32) -> f32 {
+    posicao as f32 - POSICAO_CENTRO as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ganhos_nao_finitos() {
+        let mut pid = Pid::new(GanhosPid::new(1.0, 2.0, 0.0), 1000.0, 300.0, 0.0);
+        assert!(!pid.define_ganhos(GanhosPid::new(1.0, f32::NAN, 0.0)));
+        assert!(!pid.define_ganhos(GanhosPid::new(f32::INFINITY, 0.0, 0.0)));
+        assert_eq!(pid.ganhos(), GanhosPid::new(1.0, 2.0, 0.0));
+        assert!(pid.atualiza(10.0, 0.001).is_finite());
+
+        let pid = Pid::new(GanhosPid::new(0.0, f32::NAN, 0.0), 1000.0, 300.0, 0.0);
+        assert_eq!(pid.ganhos(), GanhosPid::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn integral_limitada() {
+        // Limite da contribuição 300 com ki 2: integral presa em 150
+        let mut pid = Pid::new(GanhosPid::new(0.0, 2.0, 0.0), 1000.0, 300.0, 0.0);
+        for _ in 0..1000 {
+            pid.atualiza(500.0, 0.01);
+        }
+        assert_eq!(pid.integral(), 150.0);
+        assert_eq!(pid.atualiza(500.0, 0.01), 300.0);
+        // Saindo da saturação o erro oposto já começa a descontar
+        pid.atualiza(-100.0, 0.01);
+        assert!(pid.integral() < 150.0);
+        for _ in 0..1000 {
+            pid.atualiza(-500.0, 0.01);
+        }
+        assert_eq!(pid.integral(), -150.0);
+    }
+
+    #[test]
+    fn sem_pico_da_derivada_depois_do_reset() {
+        let mut pid = Pid::new(GanhosPid::new(0.0, 0.0, 1.0), 1000.0, 300.0, 0.0);
+        assert_eq!(pid.atualiza(200.0, 0.001), 0.0);
+        pid.atualiza(0.0, 0.001);
+        pid.reset();
+        // O erro anterior antes do reset não conta
+        assert_eq!(pid.atualiza(500.0, 0.001), 0.0);
+        assert_eq!(pid.atualiza(500.0, 0.001), 0.0);
+    }
+
+    #[test]
+    fn derivada_filtrada() {
+        let degrau = |alfa| {
+            let mut pid = Pid::new(GanhosPid::new(0.0, 0.0, 0.001), 1e6, 300.0, alfa);
+            pid.atualiza(0.0, 0.001);
+            (0..4).map(|_| pid.atualiza(100.0, 0.001)).collect::<std::vec::Vec<_>>()
+        };
+        // Sem filtro: kd * 100 / 0,001 no degrau e zero depois
+        assert_eq!(degrau(0.0), [100.0, 0.0, 0.0, 0.0]);
+        // Com alfa 0,5 o degrau entra pela metade e decai pela metade a cada amostra
+        let filtrada = degrau(0.5);
+        for (v, esperado) in filtrada.iter().zip([50.0, 25.0, 12.5, 6.25]) {
+            assert!((v - esperado).abs() < 1e-3, "{:?}", filtrada);
+        }
+    }
+
+    #[test]
+    fn saida_saturada() {
+        let mut pid = Pid::new(GanhosPid::new(10.0, 0.0, 0.0), 1000.0, 300.0, 0.0);
+        assert_eq!(pid.atualiza(50.0, 0.001), 500.0);
+        assert_eq!(pid.atualiza(3500.0, 0.001), 1000.0);
+        assert_eq!(pid.atualiza(-3500.0, 0.001), -1000.0);
+    }
+
+    #[test]
+    fn integral_zerada_sem_ki() {
+        let mut pid = Pid::new(GanhosPid::new(0.0, 1.0, 0.0), 1000.0, 300.0, 0.0);
+        for _ in 0..10 {
+            pid.atualiza(100.0, 0.01);
+        }
+        assert!(pid.integral() > 0.0);
+        assert!(pid.define_ganhos(GanhosPid::new(0.0, 0.0, 0.0)));
+        pid.atualiza(100.0, 0.01);
+        assert_eq!(pid.integral(), 0.0);
+        // Voltando o ki a integral recomeça do zero
+        assert!(pid.define_ganhos(GanhosPid::new(0.0, 1.0, 0.0)));
+        pid.atualiza(100.0, 0.01);
+        assert!((pid.integral() - 1.0).abs() < 1e-6);
+    }
+}
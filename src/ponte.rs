@@ -0,0 +1,159 @@
+// Driver da ponte H dupla: PWM no TIM3 (CH3 esquerdo, CH4 direito) e dois pinos de direção por motor
+//
+// IN1 IN2
+//  1   0   frente
+//  0   1   ré
+//  1   1   freio
+//  0   0   livre
+
+use embassy_stm32::gpio::{Level, Output, OutputType, Speed};
+use embassy_stm32::peripherals;
+use embassy_stm32::time::{hz, Hertz};
+use embassy_stm32::timer::low_level::CountingMode;
+use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm};
+use embassy_stm32::timer::Channel as CanalPwm;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
+use crate::motores::{ComandoMotores, Lado, Parada, DUTY_MAX, FREQUENCIA_MAX_HZ, FREQUENCIA_MIN_HZ};
+
+// Fila de comandos para a tarefa dona dos motores
+static COMANDOS: Channel<CriticalSectionRawMutex, ComandoMotores, 4> = Channel::new();
+
+// Envia um comando para a tarefa dos motores
+pub async fn comanda(comando: ComandoMotores) {
+    COMANDOS.send(comando).await;
+}
+
+// Duty dos dois motores em uma única chamada, usada pela malha de controle
+pub async fn aciona(esquerdo: i16, direito: i16) {
+    comanda(ComandoMotores::Duty { esquerdo, direito }).await;
+}
+
+pub async fn para(parada: Parada) {
+    comanda(ComandoMotores::Para(parada)).await;
+}
+
+struct Ponte {
+    in1: Output<'static>,
+    in2: Output<'static>,
+    canal: CanalPwm,
+    duty: i16,
+}
+
+pub struct Motores {
+    pwm: SimplePwm<'static, peripherals::TIM3>,
+    esquerdo: Ponte,
+    direito: Ponte,
+    parada: Parada,
+}
+
+impl Motores {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tim: peripherals::TIM3,
+        pwm_esquerdo: peripherals::PB0,
+        pwm_direito: peripherals::PB1,
+        esquerdo_in1: peripherals::PB13,
+        esquerdo_in2: peripherals::PB14,
+        direito_in1: peripherals::PB15,
+        direito_in2: peripherals::PB10,
+        frequencia: Hertz,
+    ) -> Self {
+        let ch3 = PwmPin::new_ch3(pwm_esquerdo, OutputType::PushPull);
+        let ch4 = PwmPin::new_ch4(pwm_direito, OutputType::PushPull);
+        let mut pwm = SimplePwm::new(tim, None, None, Some(ch3), Some(ch4), frequencia, CountingMode::EdgeAlignedUp);
+        pwm.ch3().set_duty_cycle_fully_off();
+        pwm.ch4().set_duty_cycle_fully_off();
+        pwm.ch3().enable();
+        pwm.ch4().enable();
+
+        Self {
+            pwm,
+            esquerdo: Ponte {
+                in1: Output::new(esquerdo_in1, Level::Low, Speed::Low),
+                in2: Output::new(esquerdo_in2, Level::Low, Speed::Low),
+                canal: CanalPwm::Ch3,
+                duty: 0,
+            },
+            direito: Ponte {
+                in1: Output::new(direito_in1, Level::Low, Speed::Low),
+                in2: Output::new(direito_in2, Level::Low, Speed::Low),
+                canal: CanalPwm::Ch4,
+                duty: 0,
+            },
+            parada: Parada::Livre,
+        }
+    }
+
+    pub fn aciona(&mut self, lado: Lado, duty: i16) {
+        let duty = duty.clamp(-DUTY_MAX, DUTY_MAX);
+        let parada = self.parada;
+        let ponte = match lado {
+            Lado::Esquerdo => &mut self.esquerdo,
+            Lado::Direito => &mut self.direito,
+        };
+        ponte.duty = duty;
+
+        match (duty.signum(), parada) {
+            (1, _) => {
+                ponte.in1.set_high();
+                ponte.in2.set_low();
+            }
+            (-1, _) => {
+                ponte.in1.set_low();
+                ponte.in2.set_high();
+            }
+            (_, Parada::Freio) => {
+                ponte.in1.set_high();
+                ponte.in2.set_high();
+            }
+            (_, Parada::Livre) => {
+                ponte.in1.set_low();
+                ponte.in2.set_low();
+            }
+        }
+
+        let mut canal = self.pwm.channel(ponte.canal);
+        let max = canal.max_duty_cycle() as u32;
+        canal.set_duty_cycle((duty.unsigned_abs() as u32 * max / DUTY_MAX as u32) as u16);
+    }
+
+    // Zera os dois motores; com Freio os terminais ficam em curto, com Livre ficam abertos
+    pub fn para(&mut self, parada: Parada) {
+        self.parada = parada;
+        self.aciona(Lado::Esquerdo, 0);
+        self.aciona(Lado::Direito, 0);
+    }
+
+    // Mudar a frequência muda o período do timer, então o duty atual é reaplicado
+    pub fn set_frequencia(&mut self, frequencia: Hertz) {
+        self.pwm.set_frequency(frequencia);
+        self.aciona(Lado::Esquerdo, self.esquerdo.duty);
+        self.aciona(Lado::Direito, self.direito.duty);
+    }
+
+    pub fn duty(&self) -> (i16, i16) {
+        (self.esquerdo.duty, self.direito.duty)
+    }
+
+    pub fn executa_comando(&mut self, comando: ComandoMotores) {
+        match comando {
+            ComandoMotores::Duty { esquerdo, direito } => {
+                self.aciona(Lado::Esquerdo, esquerdo);
+                self.aciona(Lado::Direito, direito);
+            }
+            ComandoMotores::Motor(lado, duty) => self.aciona(lado, duty),
+            ComandoMotores::Para(parada) => self.para(parada),
+            ComandoMotores::Frequencia(f) => self.set_frequencia(hz(f.clamp(FREQUENCIA_MIN_HZ, FREQUENCIA_MAX_HZ))),
+        }
+    }
+
+    // Loop da tarefa dona do driver: aplica os comandos conforme chegam
+    pub async fn executa(&mut self) -> ! {
+        loop {
+            let comando = COMANDOS.receive().await;
+            self.executa_comando(comando);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// Processamento do array de 8 sensores de linha (PA0..PA7)
+
+pub const NUM_SENSORES: usize = 8;
+
+pub const PESOS: [u32; NUM_SENSORES] = [0, 1000, 2000, 3000, 4000, 5000, 6000, 7000];
+
+pub const POSICAO_MAX: u32 = PESOS[NUM_SENSORES - 1];
+pub const POSICAO_CENTRO: u32 = POSICAO_MAX / 2;
+
+// Maior valor do ADC em 12 bits
+pub const ADC_MAX: u16 = 4095;
+
+// Faixa de saída de um sensor depois de normalizado pela calibração
+pub const ESCALA_NORMALIZADA: u16 = 1000;
+
+// Diferença mínima entre máximo e mínimo para considerar um canal calibrado.
+// Abaixo disso o sensor não viu a linha durante a varredura.
+pub const FAIXA_MINIMA: u16 = 100;
+
+// Leitura normalizada a partir da qual um sensor está sobre a linha
+pub const LIMIAR_LINHA: u16 = 500;
+
+// Mínimo e máximo de cada canal registrados durante a varredura da calibração
+#[derive(Clone, Copy)]
+pub struct Calibracao {
+    pub min: [u16; NUM_SENSORES],
+    pub max: [u16; NUM_SENSORES],
+    pub amostras: u32,
+}
+
+impl Calibracao {
+    pub const fn new() -> Self {
+        Self {
+            min: [u16::MAX; NUM_SENSORES],
+            max: [0; NUM_SENSORES],
+            amostras: 0,
+        }
+    }
+
+    pub fn limpa(&mut self) {
+        *self = Self::new();
+    }
+
+    // Atualiza mínimo e máximo de cada canal com uma nova leitura
+    pub fn registra(&mut self, leituras: &[u16; NUM_SENSORES]) {
+        for (i, &valor) in leituras.iter().enumerate() {
+            self.min[i] = self.min[i].min(valor);
+            self.max[i] = self.max[i].max(valor);
+        }
+        self.amostras = self.amostras.saturating_add(1);
+    }
+
+    pub fn canal_valido(&self, canal: usize) -> bool {
+        self.max[canal] > self.min[canal] && self.max[canal] - self.min[canal] >= FAIXA_MINIMA
+    }
+
+    // A calibração só é usada se todos os canais viram a linha e o fundo
+    pub fn valida(&self) -> bool {
+        self.amostras > 0 && (0..NUM_SENSORES).all(|i| self.canal_valido(i))
+    }
+
+    // Converte as leituras brutas para 0..ESCALA_NORMALIZADA usando min/max de cada canal
+    pub fn normaliza(&self, leituras: &[u16; NUM_SENSORES]) -> [u16; NUM_SENSORES] {
+        let mut normalizado = [0u16; NUM_SENSORES];
+
+        for (i, &valor) in leituras.iter().enumerate() {
+            if !self.canal_valido(i) {
+                continue;
+            }
+            let min = self.min[i] as u32;
+            let faixa = self.max[i] as u32 - min;
+            let valor = (valor as u32).clamp(min, self.max[i] as u32);
+            normalizado[i] = ((valor - min) * ESCALA_NORMALIZADA as u32 / faixa) as u16;
+        }
+        normalizado
+    }
+}
+
+impl Default for Calibracao {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Converte leituras brutas de 12 bits para a mesma escala da calibração,
+// usado enquanto não existe uma calibração válida
+pub fn escala_bruta(leituras: &[u16; NUM_SENSORES]) -> [u16; NUM_SENSORES] {
+    let mut escalado = [0u16; NUM_SENSORES];
+
+    for (i, &valor) in leituras.iter().enumerate() {
+        let valor = (valor as u32).min(ADC_MAX as u32);
+        escalado[i] = (valor * ESCALA_NORMALIZADA as u32 / ADC_MAX as u32) as u16;
+    }
+    escalado
+}
+
+// Média ponderada das leituras; None quando todos os sensores leem zero
+pub fn calcula_posicao_peso(sensores: &[u16; NUM_SENSORES]) -> Option<u32> {
+    let mut soma_pesos = 0u32;
+    let mut soma_valores = 0u32;
+
+    for (i, &valor) in sensores.iter().enumerate() {
+        soma_pesos += valor as u32 * PESOS[i];
+        soma_valores += valor as u32;
+    }
+    soma_pesos.checked_div(soma_valores)
+}
+
+// Resultado da estimativa de posição da linha
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum LeituraLinha {
+    // Linha sob o array, posição em 0..=POSICAO_MAX
+    NaLinha(u32),
+    // Nenhum sensor vê a linha, vista pela última vez na borda esquerda
+    PerdidaEsquerda,
+    // Nenhum sensor vê a linha, vista pela última vez na borda direita
+    PerdidaDireita,
+    // Todos os sensores sobre preto (cruzamento ou marcação)
+    Intersecao,
+}
+
+impl LeituraLinha {
+    // Posição que o seguidor deve usar: quando perdida, satura no lado
+    // em que a linha sumiu para que o controle volte a ela
+    pub fn posicao(&self) -> u32 {
+        match *self {
+            LeituraLinha::NaLinha(pos) => pos,
+            LeituraLinha::PerdidaEsquerda => 0,
+            LeituraLinha::PerdidaDireita => POSICAO_MAX,
+            LeituraLinha::Intersecao => POSICAO_CENTRO,
+        }
+    }
+
+    pub fn na_linha(&self) -> bool {
+        matches!(self, LeituraLinha::NaLinha(_) | LeituraLinha::Intersecao)
+    }
+}
+
+// Estima a posição da linha lembrando de que lado ela foi vista por último
+pub struct EstimadorPosicao {
+    // Leitura normalizada a partir da qual um sensor está sobre a linha
+    pub limiar_linha: u16,
+    ultima_posicao: u32,
+}
+
+impl EstimadorPosicao {
+    pub const fn new(limiar_linha: u16) -> Self {
+        Self {
+            limiar_linha,
+            ultima_posicao: POSICAO_CENTRO,
+        }
+    }
+
+    // Recebe leituras já na escala 0..ESCALA_NORMALIZADA
+    pub fn atualiza(&mut self, sensores: &[u16; NUM_SENSORES]) -> LeituraLinha {
+        let vendo_linha = sensores.iter().filter(|&&v| v >= self.limiar_linha).count();
+
+        if vendo_linha == NUM_SENSORES {
+            return LeituraLinha::Intersecao;
+        }
+
+        match calcula_posicao_peso(sensores) {
+            Some(pos) if vendo_linha > 0 => {
+                self.ultima_posicao = pos;
+                LeituraLinha::NaLinha(pos)
+            }
+            _ if self.ultima_posicao < POSICAO_CENTRO => LeituraLinha::PerdidaEsquerda,
+            _ => LeituraLinha::PerdidaDireita,
+        }
+    }
+
+    pub fn ultima_posicao(&self) -> u32 {
+        self.ultima_posicao
+    }
+}
+
+impl Default for EstimadorPosicao {
+    fn default() -> Self {
+        Self::new(LIMIAR_LINHA)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Linha estreita sob um ou dois sensores, fundo em zero
+    fn quadro(linha: &[(usize, u16)]) -> [u16; NUM_SENSORES] {
+        let mut leituras = [0; NUM_SENSORES];
+        for &(i, v) in linha {
+            leituras[i] = v;
+        }
+        leituras
+    }
+
+    #[test]
+    fn posicao_peso() {
+        assert_eq!(calcula_posicao_peso(&[0; NUM_SENSORES]), None);
+        assert_eq!(calcula_posicao_peso(&quadro(&[(0, 1000)])), Some(0));
+        assert_eq!(calcula_posicao_peso(&quadro(&[(3, 1000), (4, 1000)])), Some(3500));
+        assert_eq!(calcula_posicao_peso(&quadro(&[(6, 500), (7, 1000)])), Some(6666));
+    }
+
+    #[test]
+    fn na_linha_e_intersecao() {
+        let mut e = EstimadorPosicao::default();
+        assert_eq!(e.atualiza(&quadro(&[(2, 1000), (3, 1000)])), LeituraLinha::NaLinha(2500));
+        assert_eq!(e.ultima_posicao(), 2500);
+
+        let leitura = e.atualiza(&[900; NUM_SENSORES]);
+        assert_eq!(leitura, LeituraLinha::Intersecao);
+        assert_eq!(leitura.posicao(), POSICAO_CENTRO);
+        // A interseção não muda o lado lembrado
+        assert_eq!(e.ultima_posicao(), 2500);
+    }
+
+    #[test]
+    fn abaixo_do_limiar_nao_e_linha() {
+        let mut e = EstimadorPosicao::default();
+        e.atualiza(&quadro(&[(7, 1000)]));
+        // Ruído fraco no lado oposto não conta como linha
+        assert_eq!(e.atualiza(&quadro(&[(0, 300)])), LeituraLinha::PerdidaDireita);
+    }
+
+    #[test]
+    fn memoria_da_linha_perdida() {
+        let mut e = EstimadorPosicao::default();
+        e.atualiza(&quadro(&[(0, 1000), (1, 400)]));
+        let leitura = e.atualiza(&[0; NUM_SENSORES]);
+        assert_eq!(leitura, LeituraLinha::PerdidaEsquerda);
+        assert_eq!(leitura.posicao(), 0);
+        assert!(!leitura.na_linha());
+        // Continua lembrando até voltar a ver a linha
+        assert_eq!(e.atualiza(&[0; NUM_SENSORES]), LeituraLinha::PerdidaEsquerda);
+
+        e.atualiza(&quadro(&[(6, 1000), (7, 1000)]));
+        let leitura = e.atualiza(&[0; NUM_SENSORES]);
+        assert_eq!(leitura, LeituraLinha::PerdidaDireita);
+        assert_eq!(leitura.posicao(), POSICAO_MAX);
+    }
+
+    #[test]
+    fn normalizacao() {
+        let mut cal = Calibracao::new();
+        cal.registra(&[200; NUM_SENSORES]);
+        cal.registra(&[3200; NUM_SENSORES]);
+        assert!(cal.valida());
+        assert_eq!(cal.normaliza(&[1700; NUM_SENSORES]), [500; NUM_SENSORES]);
+        assert_eq!(cal.normaliza(&[4000; NUM_SENSORES]), [ESCALA_NORMALIZADA; NUM_SENSORES]);
+    }
+}
@@ -0,0 +1,213 @@
+// Registro das tarefas dos executores com o tempo de CPU de cada uma
+//
+// Alimentado pelos ganchos de trace do embassy-executor: cada tarefa é
+// identificada pelo endereço dela no executor e os tempos vêm do contador de
+// ciclos lido antes e depois de cada poll.
+
+use heapless::Vec;
+
+pub const MAX_TAREFAS: usize = 24;
+// Polls aninhados: um por executor que pode interromper o outro
+const MAX_ANINHADOS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum EstadoTarefa {
+    // Acordada, na fila do executor
+    Pronta,
+    Executando,
+    // Esperando um evento (timer, sinal, canal...)
+    Esperando,
+}
+
+impl EstadoTarefa {
+    pub fn nome(&self) -> &'static str {
+        match self {
+            EstadoTarefa::Pronta => "pronta",
+            EstadoTarefa::Executando => "executando",
+            EstadoTarefa::Esperando => "esperando",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InfoTarefa {
+    pub id: u32,
+    pub executor: u32,
+    pub nome: &'static str,
+    pub prioridade: &'static str,
+    // Bytes da tarefa na arena do executor (futuro e cabeçalho)
+    pub tamanho: usize,
+    pub estado: EstadoTarefa,
+    pub polls: u32,
+    pub ciclos: u64,
+    // Poll mais longo, para achar quem segura o executor
+    pub ciclos_max: u32,
+    // Ciclo em que foi acordada pela última vez (liberação da ativação atual)
+    pub acordou: u32,
+    pub inicio_poll: u32,
+}
+
+// Um poll em andamento; os ciclos dos polls que o interromperam não contam para ele
+#[derive(Clone, Copy)]
+struct PollAberto {
+    indice: usize,
+    interrompido: u32,
+}
+
+pub struct RegistroTarefas {
+    tarefas: Vec<InfoTarefa, MAX_TAREFAS>,
+    // Polls em andamento, o do topo é o que está rodando agora
+    executando: Vec<PollAberto, MAX_ANINHADOS>,
+    ultimo_ciclo: u32,
+    ciclos_total: u64,
+}
+
+impl RegistroTarefas {
+    pub const fn new() -> Self {
+        Self {
+            tarefas: Vec::new(),
+            executando: Vec::new(),
+            ultimo_ciclo: 0,
+            ciclos_total: 0,
+        }
+    }
+
+    // Estende o contador de 32 bits; basta ser chamado mais de uma vez por volta
+    // dele (cerca de 268 s nos 16 MHz do HSI), o que os polls garantem
+    pub fn avanca(&mut self, agora: u32) -> u64 {
+        self.ciclos_total += agora.wrapping_sub(self.ultimo_ciclo) as u64;
+        self.ultimo_ciclo = agora;
+        self.ciclos_total
+    }
+
+    pub fn nova(&mut self, executor: u32, id: u32, agora: u32) {
+        self.avanca(agora);
+        let _ = self.tarefas.push(InfoTarefa {
+            id,
+            executor,
+            nome: "?",
+            prioridade: "?",
+            tamanho: 0,
+            estado: EstadoTarefa::Pronta,
+            polls: 0,
+            ciclos: 0,
+            ciclos_max: 0,
+            acordou: agora,
+            inicio_poll: agora,
+        });
+    }
+
+    // O spawn chama o gancho de tarefa nova na hora, então a última registrada
+    // é a que acabou de ser criada
+    pub fn nomeia_ultima(&mut self, nome: &'static str, prioridade: &'static str, tamanho: usize) {
+        if let Some(tarefa) = self.tarefas.last_mut() {
+            tarefa.nome = nome;
+            tarefa.prioridade = prioridade;
+            tarefa.tamanho = tamanho;
+        }
+    }
+
+    pub fn pronta(&mut self, id: u32, agora: u32) {
+        if let Some(tarefa) = self.procura(id) {
+            tarefa.estado = EstadoTarefa::Pronta;
+            tarefa.acordou = agora;
+        }
+    }
+
+    pub fn inicio_poll(&mut self, id: u32, agora: u32) {
+        self.avanca(agora);
+        if let Some(indice) = self.tarefas.iter().position(|t| t.id == id) {
+            let tarefa = &mut self.tarefas[indice];
+            tarefa.estado = EstadoTarefa::Executando;
+            tarefa.inicio_poll = agora;
+            let _ = self.executando.push(PollAberto { indice, interrompido: 0 });
+        }
+    }
+
+    pub fn fim_poll(&mut self, id: u32, agora: u32) {
+        self.avanca(agora);
+        let mut interrompido = 0;
+        if let Some(aberto) = self.executando.last() {
+            if self.tarefas[aberto.indice].id == id {
+                interrompido = aberto.interrompido;
+                self.executando.pop();
+            }
+        }
+        if let Some(tarefa) = self.tarefas.iter_mut().find(|t| t.id == id) {
+            let duracao = agora.wrapping_sub(tarefa.inicio_poll);
+            // O poll de baixo fica parado enquanto este roda
+            if let Some(abaixo) = self.executando.last_mut() {
+                abaixo.interrompido = abaixo.interrompido.wrapping_add(duracao);
+            }
+            let ciclos = duracao.wrapping_sub(interrompido);
+            tarefa.polls = tarefa.polls.wrapping_add(1);
+            tarefa.ciclos += ciclos as u64;
+            tarefa.ciclos_max = tarefa.ciclos_max.max(ciclos);
+            // Se acordou a si mesma durante o poll continua na fila
+            if tarefa.estado == EstadoTarefa::Executando {
+                tarefa.estado = EstadoTarefa::Esperando;
+            }
+        }
+    }
+
+    // Tarefa em poll no momento, para quem roda dentro dela saber quando foi acordada
+    pub fn atual(&self) -> Option<&InfoTarefa> {
+        self.executando.last().map(|aberto| &self.tarefas[aberto.indice])
+    }
+
+    pub fn tarefas(&self) -> &[InfoTarefa] {
+        &self.tarefas
+    }
+
+    // Soma das tarefas na arena, sem o alinhamento entre elas
+    pub fn tamanho_total(&self) -> usize {
+        self.tarefas.iter().map(|t| t.tamanho).sum()
+    }
+
+    pub fn ciclos_total(&self) -> u64 {
+        self.ciclos_total
+    }
+
+    // Parcela do tempo total usada pela tarefa, em décimos de por cento
+    pub fn permil(&self, tarefa: &InfoTarefa) -> u32 {
+        if self.ciclos_total == 0 {
+            return 0;
+        }
+        (tarefa.ciclos * 1000 / self.ciclos_total) as u32
+    }
+
+    fn procura(&mut self, id: u32) -> Option<&mut InfoTarefa> {
+        self.tarefas.iter_mut().find(|t| t.id == id)
+    }
+}
+
+impl Default for RegistroTarefas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_aninhado_desconta_a_interrupcao() {
+        let mut r = RegistroTarefas::new();
+        r.nova(0, 1, 0);
+        r.nova(1, 2, 0);
+        r.inicio_poll(1, 100);
+        // O executor de alta prioridade interrompe no meio do poll
+        r.inicio_poll(2, 150);
+        assert_eq!(r.atual().map(|t| t.id), Some(2));
+        r.fim_poll(2, 250);
+        assert_eq!(r.atual().map(|t| t.id), Some(1));
+        r.fim_poll(1, 300);
+
+        assert_eq!(r.tarefas()[0].ciclos, 100);
+        assert_eq!(r.tarefas()[0].ciclos_max, 100);
+        assert_eq!(r.tarefas()[1].ciclos, 100);
+        let soma: u32 = r.tarefas().iter().map(|t| r.permil(t)).sum();
+        assert!(soma <= 1000);
+    }
+}
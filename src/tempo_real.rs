@@ -0,0 +1,333 @@
+// Monitor de prazos das tarefas periódicas de tempo real
+//
+// Cada tarefa declara período e prazo e marca o início e o fim de cada
+// ativação. A liberação é o momento em que o executor acordou a tarefa (vem
+// do registro de tarefas), ou um período depois da anterior quando a tarefa
+// atrasada roda de novo sem ser acordada; os tempos são em ciclos do DWT.
+//
+// As últimas ativações ficam num rastro circular, que congela na primeira
+// perda de prazo para mostrar o que aconteceu logo antes dela.
+
+use heapless::{Deque, Vec};
+
+pub const MAX_TAREFAS_RT: usize = 4;
+// A última faixa junta tudo o que passou do fim do histograma
+pub const NUM_FAIXAS: usize = 16;
+pub const TAMANHO_RASTRO: usize = 32;
+
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct Faixa {
+    pub min: u32,
+    pub max: u32,
+    soma: u64,
+    // Em u64 como a soma: a 1 kHz um u32 estouraria em uns 49 dias
+    amostras: u64,
+}
+
+impl Faixa {
+    pub const fn new() -> Self {
+        Self {
+            min: u32::MAX,
+            max: 0,
+            soma: 0,
+            amostras: 0,
+        }
+    }
+
+    pub fn registra(&mut self, valor: u32) {
+        self.min = self.min.min(valor);
+        self.max = self.max.max(valor);
+        self.soma += valor as u64;
+        self.amostras += 1;
+    }
+
+    pub fn media(&self) -> u32 {
+        if self.amostras == 0 {
+            return 0;
+        }
+        (self.soma / self.amostras) as u32
+    }
+
+    // Sem amostras o mínimo fica em zero em vez de u32::MAX
+    pub fn minimo(&self) -> u32 {
+        if self.amostras == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+}
+
+impl Default for Faixa {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Histograma {
+    // Ciclos por faixa
+    pub largura: u32,
+    pub contagens: [u32; NUM_FAIXAS],
+}
+
+impl Histograma {
+    pub const fn new(largura: u32) -> Self {
+        Self {
+            largura: if largura == 0 { 1 } else { largura },
+            contagens: [0; NUM_FAIXAS],
+        }
+    }
+
+    // Com largura calculada para que o alcance caiba nas faixas antes da última
+    pub const fn cobrindo(alcance: u32) -> Self {
+        Self::new(alcance.div_ceil(NUM_FAIXAS as u32 - 1))
+    }
+
+    pub fn registra(&mut self, valor: u32) {
+        let faixa = ((valor / self.largura) as usize).min(NUM_FAIXAS - 1);
+        self.contagens[faixa] = self.contagens[faixa].saturating_add(1);
+    }
+
+    pub fn maior(&self) -> u32 {
+        self.contagens.iter().copied().max().unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct EventoRt {
+    // Índice da tarefa no monitor
+    pub tarefa: usize,
+    pub liberacao: u32,
+    pub inicio: u32,
+    pub fim: u32,
+    pub perdeu: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TarefaRt {
+    pub nome: &'static str,
+    pub periodo: u32,
+    // Contado a partir da liberação
+    pub prazo: u32,
+    pub ativacoes: u32,
+    pub perdas: u32,
+    // Da liberação ao início do poll
+    pub latencia: Faixa,
+    // Do início do poll ao fim da ativação
+    pub execucao: Faixa,
+    // Da liberação ao fim, comparado com o prazo
+    pub resposta: Faixa,
+    // Diferença entre o intervalo de duas liberações e o período
+    pub jitter: Faixa,
+    // Execução até o prazo e jitter até meio período
+    pub hist_execucao: Histograma,
+    pub hist_jitter: Histograma,
+    liberacao: u32,
+    inicio: u32,
+    ultima_liberacao: Option<u32>,
+    // Quando o executor acordou a tarefa da última vez, como veio do registro
+    ultimo_acordar: Option<u32>,
+    em_andamento: bool,
+}
+
+impl TarefaRt {
+    fn new(nome: &'static str, periodo: u32, prazo: u32) -> Self {
+        Self {
+            nome,
+            periodo,
+            prazo,
+            ativacoes: 0,
+            perdas: 0,
+            latencia: Faixa::new(),
+            execucao: Faixa::new(),
+            resposta: Faixa::new(),
+            jitter: Faixa::new(),
+            hist_execucao: Histograma::cobrindo(prazo),
+            hist_jitter: Histograma::cobrindo(periodo / 2),
+            liberacao: 0,
+            inicio: 0,
+            ultima_liberacao: None,
+            ultimo_acordar: None,
+            em_andamento: false,
+        }
+    }
+
+    fn zera(&mut self) {
+        *self = Self::new(self.nome, self.periodo, self.prazo);
+    }
+}
+
+pub struct MonitorRt {
+    tarefas: Vec<TarefaRt, MAX_TAREFAS_RT>,
+    rastro: Deque<EventoRt, TAMANHO_RASTRO>,
+    congelado: bool,
+}
+
+impl MonitorRt {
+    pub const fn new() -> Self {
+        Self {
+            tarefas: Vec::new(),
+            rastro: Deque::new(),
+            congelado: false,
+        }
+    }
+
+    // Devolve o índice usado em inicio/fim; a mesma tarefa registrada de novo
+    // (reiniciada) reaproveita a entrada
+    pub fn registra(&mut self, nome: &'static str, periodo: u32, prazo: u32) -> Option<usize> {
+        if let Some(indice) = self.indice(nome) {
+            let tarefa = &mut self.tarefas[indice];
+            tarefa.periodo = periodo;
+            tarefa.prazo = prazo;
+            tarefa.zera();
+            return Some(indice);
+        }
+        self.tarefas.push(TarefaRt::new(nome, periodo, prazo)).ok()?;
+        Some(self.tarefas.len() - 1)
+    }
+
+    // Um acordar igual ao anterior quer dizer que a tarefa não foi acordada de
+    // novo: o ticker atrasado volta na hora. A ativação conta do mesmo jeito,
+    // liberada onde deveria, um período depois da anterior
+    pub fn inicio(&mut self, id: usize, acordou: u32, inicio: u32) {
+        let Some(tarefa) = self.tarefas.get_mut(id) else {
+            return;
+        };
+        let liberacao = match tarefa.ultima_liberacao {
+            Some(anterior) if tarefa.ultimo_acordar == Some(acordou) => anterior.wrapping_add(tarefa.periodo),
+            _ => acordou,
+        };
+        tarefa.ultimo_acordar = Some(acordou);
+        if let Some(anterior) = tarefa.ultima_liberacao {
+            let jitter = liberacao.wrapping_sub(anterior).abs_diff(tarefa.periodo);
+            tarefa.jitter.registra(jitter);
+            tarefa.hist_jitter.registra(jitter);
+        }
+        tarefa.ultima_liberacao = Some(liberacao);
+        tarefa.liberacao = liberacao;
+        tarefa.inicio = inicio;
+        tarefa.em_andamento = true;
+        tarefa.latencia.registra(inicio.wrapping_sub(liberacao));
+    }
+
+    // Fecha a ativação; devolve true se o prazo foi perdido
+    pub fn fim(&mut self, id: usize, agora: u32) -> bool {
+        let Some(tarefa) = self.tarefas.get_mut(id) else {
+            return false;
+        };
+        if !core::mem::take(&mut tarefa.em_andamento) {
+            return false;
+        }
+        let resposta = agora.wrapping_sub(tarefa.liberacao);
+        let execucao = agora.wrapping_sub(tarefa.inicio);
+        tarefa.ativacoes = tarefa.ativacoes.wrapping_add(1);
+        tarefa.execucao.registra(execucao);
+        tarefa.hist_execucao.registra(execucao);
+        tarefa.resposta.registra(resposta);
+        let perdeu = resposta > tarefa.prazo;
+        if perdeu {
+            tarefa.perdas = tarefa.perdas.wrapping_add(1);
+        }
+
+        if !self.congelado {
+            if self.rastro.is_full() {
+                self.rastro.pop_front();
+            }
+            let _ = self.rastro.push_back(EventoRt {
+                tarefa: id,
+                liberacao: tarefa.liberacao,
+                inicio: tarefa.inicio,
+                fim: agora,
+                perdeu,
+            });
+            self.congelado = perdeu;
+        }
+        perdeu
+    }
+
+    pub fn indice(&self, nome: &str) -> Option<usize> {
+        self.tarefas.iter().position(|t| t.nome == nome)
+    }
+
+    pub fn tarefas(&self) -> &[TarefaRt] {
+        &self.tarefas
+    }
+
+    // Ativações mais antigas primeiro; a última é a perda que congelou o rastro
+    pub fn rastro(&self) -> impl Iterator<Item = &EventoRt> {
+        self.rastro.iter()
+    }
+
+    pub fn congelado(&self) -> bool {
+        self.congelado
+    }
+
+    // Zera as estatísticas e volta a gravar o rastro
+    pub fn zera(&mut self) {
+        self.tarefas.iter_mut().for_each(TarefaRt::zera);
+        self.rastro.clear();
+        self.congelado = false;
+    }
+}
+
+impl Default for MonitorRt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ativacao_atrasada_conta_como_perda() {
+        let mut rt = MonitorRt::new();
+        let id = rt.registra("t", 1000, 500).unwrap();
+        rt.inicio(id, 0, 10);
+        assert!(!rt.fim(id, 100));
+        // Estourou: a ativação liberada em 1000 termina depois do prazo
+        rt.inicio(id, 1000, 1010);
+        assert!(rt.fim(id, 2100));
+
+        // O ticker atrasado volta sem novo acordar e repete a liberação 1000;
+        // a ativação vale como liberada em 2000 e também perde o prazo
+        rt.inicio(id, 1000, 2100);
+        assert!(rt.fim(id, 2600));
+        // Ainda atrasada: liberada em 3000, termina dentro do prazo
+        rt.inicio(id, 1000, 3000);
+        assert!(!rt.fim(id, 3100));
+        let tarefa = &rt.tarefas()[id];
+        assert_eq!(tarefa.ativacoes, 4);
+        assert_eq!(tarefa.perdas, 2);
+        assert_eq!(tarefa.resposta.max, 1100);
+        assert_eq!(tarefa.latencia.max, 100);
+        assert_eq!(tarefa.jitter.max, 0);
+        assert_eq!(tarefa.hist_execucao.contagens.iter().sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn registro_cheio() {
+        let mut rt = MonitorRt::new();
+        let nomes = ["a", "b", "c", "d"];
+        for nome in nomes {
+            assert!(rt.registra(nome, 1000, 1000).is_some());
+        }
+        assert_eq!(rt.registra("e", 1000, 1000), None);
+        // Reiniciar uma tarefa já registrada continua funcionando
+        assert_eq!(rt.registra("b", 2000, 2000), Some(1));
+    }
+
+    #[test]
+    fn faixa_alem_de_u32_amostras() {
+        let mut faixa = Faixa::new();
+        assert_eq!((faixa.minimo(), faixa.media()), (0, 0));
+        // Mais de 49 dias a 1 kHz
+        faixa.amostras = u32::MAX as u64;
+        faixa.soma = 100 * u32::MAX as u64;
+        faixa.registra(100);
+        assert_eq!(faixa.amostras, u32::MAX as u64 + 1);
+        assert_eq!(faixa.media(), 100);
+    }
+}
@@ -0,0 +1,151 @@
+// Malha PI de velocidade de uma roda: converte velocidade alvo em mm/s em duty do PWM
+
+use crate::motores::DUTY_MAX;
+
+// Velocidade alvo máxima de uma roda, acima do que os motores alcançam
+pub const VELOCIDADE_MAX_MM_S: f32 = 2000.0;
+
+// Alvo limitado a +-VELOCIDADE_MAX_MM_S; None para NaN ou infinito
+pub fn limita_alvo(alvo: f32) -> Option<f32> {
+    alvo.is_finite().then(|| alvo.clamp(-VELOCIDADE_MAX_MM_S, VELOCIDADE_MAX_MM_S))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct ParametrosVelocidade {
+    pub kp: f32,
+    pub ki: f32,
+    // Feed-forward: duty por mm/s e duty mínimo para vencer o atrito estático
+    pub kff: f32,
+    pub duty_atrito: f32,
+    // Variação máxima do alvo em mm/s²
+    pub aceleracao_max: f32,
+}
+
+impl ParametrosVelocidade {
+    pub const fn padrao() -> Self {
+        Self {
+            kp: 0.5,
+            ki: 5.0,
+            kff: 0.6,
+            duty_atrito: 50.0,
+            aceleracao_max: 3000.0,
+        }
+    }
+}
+
+impl Default for ParametrosVelocidade {
+    fn default() -> Self {
+        Self::padrao()
+    }
+}
+
+pub struct ControleVelocidade {
+    pub parametros: ParametrosVelocidade,
+    // Alvo depois da rampa de aceleração
+    alvo_rampa: f32,
+    integral: f32,
+}
+
+impl ControleVelocidade {
+    pub const fn new(parametros: ParametrosVelocidade) -> Self {
+        Self {
+            parametros,
+            alvo_rampa: 0.0,
+            integral: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.alvo_rampa = 0.0;
+        self.integral = 0.0;
+    }
+
+    pub fn alvo_rampa(&self) -> f32 {
+        self.alvo_rampa
+    }
+
+    // Uma iteração da malha; `medida` é a velocidade do encoder no mesmo período `dt`
+    pub fn atualiza(&mut self, alvo: f32, medida: f32, dt: f32) -> i16 {
+        let p = self.parametros;
+        let limite = DUTY_MAX as f32;
+
+        let passo = p.aceleracao_max * dt;
+        self.alvo_rampa += (alvo - self.alvo_rampa).clamp(-passo, passo);
+
+        let ff = if self.alvo_rampa > 0.0 {
+            p.kff * self.alvo_rampa + p.duty_atrito
+        } else if self.alvo_rampa < 0.0 {
+            p.kff * self.alvo_rampa - p.duty_atrito
+        } else {
+            0.0
+        };
+
+        let erro = self.alvo_rampa - medida;
+        let sem_integral = ff + p.kp * erro;
+        let integral = self.integral + erro * dt;
+        let saida = sem_integral + p.ki * integral;
+
+        // Anti-windup: só integra se a saída não satura, ou se o erro tira da saturação
+        if saida.abs() < limite || saida.signum() != erro.signum() {
+            self.integral = integral;
+        }
+
+        (sem_integral + p.ki * self.integral).clamp(-limite, limite) as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alvo_limitado() {
+        assert_eq!(limita_alvo(300.0), Some(300.0));
+        assert_eq!(limita_alvo(-1e9), Some(-VELOCIDADE_MAX_MM_S));
+        assert_eq!(limita_alvo(f32::NAN), None);
+        assert_eq!(limita_alvo(f32::INFINITY), None);
+    }
+
+    // Só o termo pedido ligado, sem rampa
+    fn controle(kp: f32, ki: f32, kff: f32, duty_atrito: f32) -> ControleVelocidade {
+        ControleVelocidade::new(ParametrosVelocidade { kp, ki, kff, duty_atrito, aceleracao_max: f32::INFINITY })
+    }
+
+    #[test]
+    fn rampa_de_aceleracao() {
+        let mut c = ControleVelocidade::new(ParametrosVelocidade { aceleracao_max: 3000.0, ..ParametrosVelocidade::padrao() });
+        let rampa: [f32; 5] = core::array::from_fn(|_| {
+            c.atualiza(100.0, 0.0, 0.01);
+            c.alvo_rampa()
+        });
+        assert_eq!(rampa, [30.0, 60.0, 90.0, 100.0, 100.0]);
+        c.atualiza(-100.0, 0.0, 0.01);
+        assert_eq!(c.alvo_rampa(), 70.0);
+        c.reset();
+        assert_eq!(c.alvo_rampa(), 0.0);
+    }
+
+    #[test]
+    fn feed_forward_segue_o_sinal_do_alvo() {
+        let mut c = controle(0.0, 0.0, 0.6, 50.0);
+        assert_eq!(c.atualiza(100.0, 0.0, 0.01), 110);
+        assert_eq!(c.atualiza(-100.0, 0.0, 0.01), -110);
+        // Parado não aplica o duty de atrito
+        assert_eq!(c.atualiza(0.0, 0.0, 0.01), 0);
+    }
+
+    #[test]
+    fn integral_nao_acumula_saturada() {
+        let mut c = controle(0.0, 5.0, 0.0, 0.0);
+        for _ in 0..1000 {
+            assert!(c.atualiza(VELOCIDADE_MAX_MM_S, 0.0, 0.01) <= DUTY_MAX);
+        }
+        // A integral parou no limite: a saída não sobe mais com o mesmo erro
+        let saturada = c.atualiza(VELOCIDADE_MAX_MM_S, 0.0, 0.01);
+        assert!(saturada > DUTY_MAX * 8 / 10);
+        assert_eq!(c.atualiza(VELOCIDADE_MAX_MM_S, 0.0, 0.01), saturada);
+        // Sem anti-windup a integral teria 20000 mm e a saída ficaria saturada
+        // por muitos períodos; com ele cai no primeiro erro negativo
+        assert!(c.atualiza(0.0, 100.0, 0.01) < saturada);
+    }
+}